@@ -1,9 +1,51 @@
-use bytes::BytesMut;
+use bytes::{Bytes, BytesMut};
+use std::collections::VecDeque;
+use std::fmt::Write as FmtWrite;
 
 use crate::constants::DEFAULT_LINE_BUFFER_CAPACITY;
+use crate::timestamp::Moment;
+
+/// How received bytes that are not valid UTF-8 are turned into text
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Decoding {
+    /// Invalid sequences become U+FFFD
+    Lossy,
+    /// Invalid bytes are shown as `\xNN`
+    Escaped,
+    /// Every byte maps to the Unicode code point of the same value
+    Latin1,
+    /// Bytes above 0x7F map to the IBM PC (code page 437) characters
+    Cp437,
+    /// Bytes are passed through untouched; the text is only used for matching
+    Raw,
+}
+
+/// Which byte sequences end a received line
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NewlineMode {
+    Lf,
+    Cr,
+    CrLf,
+    /// `\n`, `\r` or `\r\n`, with `\r\n` counted as a single ending
+    Any,
+}
+
+pub struct Line {
+    pub text: String,
+    pub bytes: Bytes,
+    /// When the read holding the first byte of the line returned. Reads are up to
+    /// `SERIAL_READ_BUFFER_SIZE` bytes, so lines that arrive together share this time.
+    pub received_at: Moment,
+}
 
 pub struct LineBuffer {
     buffer: BytesMut,
+    decoding: Decoding,
+    newline: NewlineMode,
+    // a line ended on `\r` at the end of the buffer, so a following `\n` belongs to it
+    skip_lf: bool,
+    // arrival time of each buffered chunk, with the number of its bytes still buffered
+    arrivals: VecDeque<(usize, Moment)>,
 }
 
 impl LineBuffer {
@@ -14,32 +56,119 @@ impl LineBuffer {
     pub fn with_capacity(capacity: usize) -> Self {
         LineBuffer {
             buffer: BytesMut::with_capacity(capacity),
+            decoding: Decoding::Lossy,
+            newline: NewlineMode::Lf,
+            skip_lf: false,
+            arrivals: VecDeque::new(),
         }
     }
     
-    pub fn write(&mut self, data_buffer: &[u8], data_size: usize) {
+    pub fn with_options(decoding: Decoding, newline: NewlineMode) -> Self {
+        LineBuffer {
+            decoding,
+            newline,
+            ..Self::new()
+        }
+    }
+    
+    /// Appends a chunk read from the port, remembering when it arrived. Every byte of the chunk
+    /// gets that time, as the port doesn't tell when each one came in.
+    pub fn write_received(&mut self, data_buffer: &[u8], data_size: usize, received_at: Moment) {
+        if data_size == 0 {
+            return;
+        }
         self.buffer.extend_from_slice(&data_buffer[..data_size]);
+        self.arrivals.push_back((data_size, received_at));
     }
     
+    #[allow(dead_code)]
+    pub fn write(&mut self, data_buffer: &[u8], data_size: usize) {
+        self.write_received(data_buffer, data_size, Moment::now());
+    }
+    
+    #[allow(dead_code)]
     pub fn next_line(&mut self) -> Option<String> {
-        let newline_pos = self.buffer.iter().position(|&b| b == b'\n')?;
-        let line_bytes = self.buffer.split_to(newline_pos + 1);
-        match String::from_utf8(line_bytes.to_vec()) {
-            Ok(line) => Some(line),
-            Err(e) => {
-                // Try to recover valid UTF-8 portion
-                let valid_up_to = e.utf8_error().valid_up_to();
-                let bytes = e.into_bytes();
-                if valid_up_to > 0 {
-                    let valid_bytes = &bytes[..valid_up_to];
-                    String::from_utf8(valid_bytes.to_vec()).ok()
-                } else {
-                    None
+        self.next_decoded_line().map(|line| line.text)
+    }
+    
+    pub fn next_decoded_line(&mut self) -> Option<Line> {
+        self.drop_pending_lf();
+        let line_len = self.find_line_end()?;
+        Some(self.split_line(line_len))
+    }
+    
+    fn split_line(&mut self, len: usize) -> Line {
+        let received_at = self.consume_arrivals(len);
+        let bytes = self.buffer.split_to(len).freeze();
+        let text = decode(&bytes, self.decoding);
+        Line { text, bytes, received_at }
+    }
+    
+    /// Drops `len` bytes from the arrival records and returns the arrival time of the first one
+    fn consume_arrivals(&mut self, mut len: usize) -> Moment {
+        let received_at = self.arrivals.front().map_or_else(Moment::now, |&(_, time)| time);
+        while len > 0 {
+            let Some(front) = self.arrivals.front_mut() else {
+                break;
+            };
+            if front.0 > len {
+                front.0 -= len;
+                break;
+            }
+            len -= front.0;
+            self.arrivals.pop_front();
+        }
+        received_at
+    }
+    
+    /// Returns the length of the first complete line, including its ending
+    fn find_line_end(&mut self) -> Option<usize> {
+        match self.newline {
+            NewlineMode::Lf => self.buffer.iter().position(|&b| b == b'\n').map(|pos| pos + 1),
+            NewlineMode::Cr => self.buffer.iter().position(|&b| b == b'\r').map(|pos| pos + 1),
+            NewlineMode::CrLf => self.buffer.windows(2).position(|pair| pair == b"\r\n").map(|pos| pos + 2),
+            NewlineMode::Any => {
+                let pos = self.buffer.iter().position(|&b| b == b'\n' || b == b'\r')?;
+                if self.buffer[pos] == b'\n' {
+                    return Some(pos + 1);
+                }
+                match self.buffer.get(pos + 1) {
+                    Some(b'\n') => Some(pos + 2),
+                    Some(_) => Some(pos + 1),
+                    None => {
+                        self.skip_lf = true;
+                        Some(pos + 1)
+                    }
                 }
             }
         }
     }
     
+    fn drop_pending_lf(&mut self) {
+        if self.skip_lf && !self.buffer.is_empty() {
+            if self.buffer[0] == b'\n' {
+                self.consume_arrivals(1);
+                let _ = self.buffer.split_to(1);
+            }
+            self.skip_lf = false;
+        }
+    }
+    
+    /// Removes and returns the buffered partial line (eg. a shell prompt) so it can be shown
+    /// before its newline arrives. The rest of the line is returned later on its own.
+    pub fn take_partial(&mut self) -> Option<Line> {
+        self.drop_pending_lf();
+        let mut partial_len = self.buffer.len();
+        if matches!(self.decoding, Decoding::Lossy | Decoding::Escaped | Decoding::Raw) {
+            // keep an incomplete UTF-8 sequence back so it is not split in two
+            partial_len -= incomplete_utf8_suffix_len(&self.buffer);
+        }
+        if partial_len == 0 {
+            return None;
+        }
+        Some(self.split_line(partial_len))
+    }
+    
     #[allow(dead_code)]
     pub fn capacity(&self) -> usize {
         self.buffer.capacity()
@@ -50,7 +179,6 @@ impl LineBuffer {
         self.buffer.len()
     }
     
-    #[allow(dead_code)]
     pub fn is_empty(&self) -> bool {
         self.buffer.is_empty()
     }
@@ -62,6 +190,61 @@ impl Default for LineBuffer {
     }
 }
 
+pub fn decode(bytes: &[u8], decoding: Decoding) -> String {
+    match decoding {
+        Decoding::Lossy | Decoding::Raw => String::from_utf8_lossy(bytes).into_owned(),
+        Decoding::Escaped => decode_escaped(bytes),
+        Decoding::Latin1 => bytes.iter().map(|&b| b as char).collect(),
+        Decoding::Cp437 => bytes.iter().map(|&b| cp437_char(b)).collect(),
+    }
+}
+
+fn incomplete_utf8_suffix_len(bytes: &[u8]) -> usize {
+    let tail_start = bytes.len().saturating_sub(3);
+    for (index, &byte) in bytes.iter().enumerate().skip(tail_start).rev() {
+        let sequence_len = match byte {
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF7 => 4,
+            0x80..=0xBF => continue,
+            _ => return 0,
+        };
+        let available = bytes.len() - index;
+        return if available < sequence_len { available } else { 0 };
+    }
+    0
+}
+
+fn decode_escaped(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        text.push_str(chunk.valid());
+        for byte in chunk.invalid() {
+            let _ = write!(text, "\\x{:02X}", byte);
+        }
+    }
+    text
+}
+
+fn cp437_char(byte: u8) -> char {
+    if byte < 0x80 {
+        byte as char
+    } else {
+        CP437_HIGH[(byte - 0x80) as usize]
+    }
+}
+
+const CP437_HIGH: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -103,6 +286,113 @@ mod tests {
         assert_eq!(buffer.len(), 0);
     }
 
+    #[test]
+    fn test_invalid_utf8_first_byte_is_kept() {
+        let mut buffer = LineBuffer::new();
+        let data = b"\xffboot\n";
+        buffer.write(data, data.len());
+        assert_eq!(buffer.next_line(), Some("\u{fffd}boot\n".to_string()));
+    }
+
+    #[test]
+    fn test_decoding_escaped() {
+        let mut buffer = LineBuffer::with_options(Decoding::Escaped, NewlineMode::Lf);
+        let data = b"ok\x80\xfe done\n";
+        buffer.write(data, data.len());
+        assert_eq!(buffer.next_line(), Some("ok\\x80\\xFE done\n".to_string()));
+    }
+
+    #[test]
+    fn test_decoding_latin1_and_cp437() {
+        let data = b"\xe9\xb0\n";
+        assert_eq!(decode(data, Decoding::Latin1), "\u{e9}\u{b0}\n");
+        assert_eq!(decode(data, Decoding::Cp437), "\u{398}\u{2591}\n");
+    }
+
+    #[test]
+    fn test_decoding_raw_keeps_bytes() {
+        let mut buffer = LineBuffer::with_options(Decoding::Raw, NewlineMode::Lf);
+        let data = b"\x1b[1m\xff\n";
+        buffer.write(data, data.len());
+        let line = buffer.next_decoded_line().unwrap();
+        assert_eq!(&line.bytes[..], &data[..]);
+    }
+
+    #[test]
+    fn test_take_partial_then_continuation() {
+        let mut buffer = LineBuffer::new();
+        let data = b"login: ";
+        buffer.write(data, data.len());
+        assert_eq!(buffer.next_line(), None);
+        assert_eq!(buffer.take_partial().map(|line| line.text), Some("login: ".to_string()));
+        assert!(buffer.is_empty());
+        buffer.write(b"root\n", 5);
+        assert_eq!(buffer.next_line(), Some("root\n".to_string()));
+    }
+
+    #[test]
+    fn test_take_partial_keeps_incomplete_utf8() {
+        let mut buffer = LineBuffer::new();
+        let data = "50\u{b0}".as_bytes();
+        buffer.write(data, data.len() - 1);
+        assert_eq!(buffer.take_partial().map(|line| line.text), Some("50".to_string()));
+        assert_eq!(buffer.len(), 1);
+        buffer.write(&data[data.len() - 1..], 1);
+        buffer.write(b"C\n", 2);
+        assert_eq!(buffer.next_line(), Some("\u{b0}C\n".to_string()));
+    }
+
+    #[test]
+    fn test_newline_mode_cr() {
+        let mut buffer = LineBuffer::with_options(Decoding::Lossy, NewlineMode::Cr);
+        let data = b"10%\r20%\r";
+        buffer.write(data, data.len());
+        assert_eq!(buffer.next_line(), Some("10%\r".to_string()));
+        assert_eq!(buffer.next_line(), Some("20%\r".to_string()));
+        assert_eq!(buffer.next_line(), None);
+    }
+
+    #[test]
+    fn test_newline_mode_crlf() {
+        let mut buffer = LineBuffer::with_options(Decoding::Lossy, NewlineMode::CrLf);
+        let data = b"a\nb\r\nc\r";
+        buffer.write(data, data.len());
+        assert_eq!(buffer.next_line(), Some("a\nb\r\n".to_string()));
+        assert_eq!(buffer.next_line(), None);
+        buffer.write(b"\n", 1);
+        assert_eq!(buffer.next_line(), Some("c\r\n".to_string()));
+    }
+
+    #[test]
+    fn test_newline_mode_any() {
+        let mut buffer = LineBuffer::with_options(Decoding::Lossy, NewlineMode::Any);
+        let data = b"one\r\ntwo\nthree\rfour\r";
+        buffer.write(data, data.len());
+        assert_eq!(buffer.next_line(), Some("one\r\n".to_string()));
+        assert_eq!(buffer.next_line(), Some("two\n".to_string()));
+        assert_eq!(buffer.next_line(), Some("three\r".to_string()));
+        assert_eq!(buffer.next_line(), Some("four\r".to_string()));
+        // the LF completing "four\r\n" arrives in the next read
+        buffer.write(b"\nfive\n", 6);
+        assert_eq!(buffer.next_line(), Some("five\n".to_string()));
+        assert_eq!(buffer.next_line(), None);
+    }
+
+    #[test]
+    fn test_lines_keep_arrival_time() {
+        let mut buffer = LineBuffer::new();
+        let first = Moment::now();
+        let second = first + std::time::Duration::from_millis(40);
+        buffer.write_received(b"boot\nkernel ", 12, first);
+        buffer.write_received(b"start\nok\n", 9, second);
+        assert_eq!(buffer.next_decoded_line().unwrap().received_at, first);
+        let line = buffer.next_decoded_line().unwrap();
+        assert_eq!(line.text, "kernel start\n");
+        assert_eq!(line.received_at, first);
+        assert_eq!(buffer.next_decoded_line().unwrap().received_at, second);
+        assert!(buffer.next_decoded_line().is_none());
+    }
+
     #[test]
     fn test_buffer_capacity() {
         let buffer = LineBuffer::with_capacity(1024);
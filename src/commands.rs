@@ -1,30 +1,62 @@
 use colored::Colorize;
 use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
-use crossterm::terminal::enable_raw_mode;
+use crossterm::terminal::{enable_raw_mode, Clear, ClearType};
+use crossterm::{cursor, queue, style::Print};
+use std::io::Write;
 use std::sync::atomic::Ordering;
 
-use crate::constants::COMMAND_POLL_PERIOD;
-use crate::settings::Settings;
+use crate::constants::{COMMAND_POLL_PERIOD, INPUT_PROMPT, REPLAY_SEEK_STEP_MS, TRANSMIT_LINE_ENDING};
+use crate::editor::LineEditor;
+use crate::macros::{self, MacroKey};
+use crate::modem;
+use crate::send;
+use crate::serial;
+use crate::settings::{self, Settings};
+use crate::share;
 use crate::state::State;
+use crate::tui::{self, Tui};
 use crate::utils::{
-    self, get_log_state, print_error, print_message, print_separator, print_warning,
+    self, get_log_state, print_error, print_line, print_message, print_separator, print_warning,
 };
 use crate::error::{Result, SpewcapError};
 
 
-pub fn command_loop(settings: Settings, shared_state: State) -> Result<()> {
+pub fn command_loop(mut settings: Settings, shared_state: State) -> Result<()> {
     if let Err(e) = enable_raw_mode() {
         print_warning(&format!(
             "Could not enable raw mode: {e}\nSome key commands may not work properly!"
         ))
     }
+    let mut editor = LineEditor::new();
+    // set by `M`, so the next digit sends a prefixed macro
+    let mut macro_prefix = false;
+    let mut tui = settings.tui.then(Tui::new);
     loop {
+        if utils::quit_requested(&shared_state) {
+            break;
+        }
+        if let Some(tui) = &mut tui {
+            if let Err(e) = tui.draw(&settings, &shared_state, &editor) {
+                print_error(&format!("Failed to draw screen: {e}"));
+            }
+        }
         let result = match poll_for_command() {
             Ok(Some((code, kind, modifiers))) => {
                 if utils::quit_requested(&shared_state) {
                     break;
                 }
-                handle_command(code, kind, modifiers, &settings, &shared_state)
+                if let Some(tui) = &mut tui {
+                    tui.request_redraw();
+                }
+                if input_mode_active(&shared_state) {
+                    handle_input_key(code, kind, modifiers, &mut editor, &shared_state)
+                } else if kind == KeyEventKind::Press
+                    && tui.as_mut().is_some_and(|tui| tui.handle_key(code, modifiers))
+                {
+                    Ok(())
+                } else {
+                    handle_command(code, kind, modifiers, &mut macro_prefix, &mut settings, &shared_state)
+                }
             }
             Ok(None) => Ok(()),
             Err(e) => Err(e),
@@ -55,9 +87,21 @@ fn handle_command(
     code: KeyCode,
     kind: KeyEventKind,
     modifiers: KeyModifiers,
-    settings: &Settings,
+    macro_prefix: &mut bool,
+    settings: &mut Settings,
     shared_state: &State,
 ) -> Result<()> {
+    if kind == KeyEventKind::Press && std::mem::take(macro_prefix) {
+        return match code {
+            KeyCode::Char(digit) if digit.is_ascii_digit() => {
+                run_key_binding(settings, shared_state, MacroKey::Prefixed(digit))
+            }
+            _ => {
+                print_message("Macro cancelled");
+                Ok(())
+            }
+        };
+    }
     if kind == KeyEventKind::Press {
         match code {
             KeyCode::Char('q') => utils::request_quit(settings, shared_state),
@@ -70,28 +114,199 @@ fn handle_command(
             KeyCode::Char('n') => utils::start_new_log(settings, shared_state)?,
             KeyCode::Char('s') => utils::save_active_log(settings, shared_state),
             KeyCode::Char('l') => toggle_pause_logging(shared_state)?,
-            KeyCode::Char('h') => help_message(),
+            KeyCode::Char('i') => toggle_input_mode(shared_state),
+            KeyCode::Char('x') => toggle_hex_display(shared_state),
+            KeyCode::Char('o') => change_port(settings, shared_state)?,
+            KeyCode::Char('b') => change_baud_rate(settings, shared_state)?,
+            KeyCode::Char('f') => change_filter(shared_state)?,
+            KeyCode::Char('h') => help_message(settings, shared_state),
+            KeyCode::Char(' ') if settings.replay.is_some() => toggle_replay_pause(shared_state),
+            KeyCode::Char('[') if settings.replay.is_some() => seek_replay(shared_state, -REPLAY_SEEK_STEP_MS),
+            KeyCode::Char(']') if settings.replay.is_some() => seek_replay(shared_state, REPLAY_SEEK_STEP_MS),
+            KeyCode::Char('m') => *macro_prefix = true,
+            KeyCode::Char('u') => send::start(shared_state)?,
+            KeyCode::Esc if send::is_active(shared_state) => send::cancel(shared_state),
+            KeyCode::Esc if modem::sequence_running(shared_state) => modem::cancel_sequence(shared_state),
+            KeyCode::Char('d') => modem::toggle_dtr(shared_state)?,
+            KeyCode::Char('r') => modem::toggle_rts(shared_state)?,
+            KeyCode::Char('k') => modem::send_break(shared_state)?,
+            KeyCode::F(number) => run_key_binding(settings, shared_state, MacroKey::Function(number))?,
             _ => {}
         }
     }
     Ok(())
 }
 
-fn help_message() {
+fn handle_input_key(
+    code: KeyCode,
+    kind: KeyEventKind,
+    modifiers: KeyModifiers,
+    editor: &mut LineEditor,
+    shared_state: &State,
+) -> Result<()> {
+    if kind != KeyEventKind::Press {
+        return Ok(());
+    }
+    match code {
+        KeyCode::Esc => {
+            editor.clear();
+            clear_input_line();
+            toggle_input_mode(shared_state);
+            return Ok(());
+        }
+        KeyCode::Enter => {
+            let mut line = editor.take_line();
+            line.push_str(TRANSMIT_LINE_ENDING);
+            clear_input_line();
+            serial::write_to_port(shared_state, line.as_bytes())?;
+        }
+        KeyCode::Char(c) if modifiers.contains(KeyModifiers::CONTROL) => {
+            // send control characters (eg. `Ctrl+C` -> 0x03) straight to the port
+            if let Some(byte) = control_byte(c) {
+                serial::write_to_port(shared_state, &[byte])?;
+            }
+        }
+        KeyCode::Char(c) => editor.insert(c),
+        KeyCode::Tab => editor.insert('\t'),
+        KeyCode::Backspace => editor.backspace(),
+        KeyCode::Delete => editor.delete(),
+        KeyCode::Left => editor.move_left(),
+        KeyCode::Right => editor.move_right(),
+        KeyCode::Home => editor.move_home(),
+        KeyCode::End => editor.move_end(),
+        KeyCode::Up => editor.history_previous(),
+        KeyCode::Down => editor.history_next(),
+        _ => {}
+    }
+    draw_input_line(editor);
+    Ok(())
+}
+
+fn control_byte(c: char) -> Option<u8> {
+    match c.to_ascii_lowercase() {
+        c @ 'a'..='z' => Some(c as u8 - b'a' + 1),
+        '@' | ' ' => Some(0x00),
+        '[' => Some(0x1b),
+        '\\' => Some(0x1c),
+        ']' => Some(0x1d),
+        _ => None,
+    }
+}
+
+fn draw_input_line(editor: &LineEditor) {
+    // the full-screen view draws the input line itself
+    if tui::is_active() {
+        return;
+    }
+    let mut stdout = std::io::stdout();
+    let prompt_len = INPUT_PROMPT.chars().count();
+    let result = queue!(
+        stdout,
+        cursor::MoveToColumn(0),
+        Clear(ClearType::CurrentLine),
+        Print(INPUT_PROMPT.cyan()),
+        Print(editor.text()),
+        cursor::MoveToColumn((prompt_len + editor.cursor()) as u16)
+    )
+    .and_then(|_| stdout.flush());
+    if let Err(e) = result {
+        print_error(&format!("Failed to draw input line: {e}"));
+    }
+}
+
+fn clear_input_line() {
+    if tui::is_active() {
+        return;
+    }
+    let mut stdout = std::io::stdout();
+    let result = queue!(stdout, cursor::MoveToColumn(0), Clear(ClearType::CurrentLine))
+        .and_then(|_| stdout.flush());
+    if let Err(e) = result {
+        print_error(&format!("Failed to clear input line: {e}"));
+    }
+}
+
+fn input_mode_active(shared_state: &State) -> bool {
+    shared_state.input_mode.load(Ordering::Relaxed)
+}
+
+fn help_message(settings: &Settings, shared_state: &State) {
     print_separator();
-    println!("Help: Use the following keys to execute commands:");
-    println!();
-    println!("- `Q` or `Ctrl+C`: Quit the application");
-    println!("- `C`: Clear the console");
-    println!("- `P`: Pause/resume capture");
-    println!("- `N`: Start a new log");
-    println!("- `L`: Pause/resume logging");
-    println!("- `S`: Save active log as...");
-    println!("- `H`: Display this help message");
-    println!();
+    print_line("Help: Use the following keys to execute commands:");
+    print_line("");
+    print_line("- `Q` or `Ctrl+C`: Quit the application");
+    print_line("- `C`: Clear the console");
+    print_line("- `P`: Pause/resume capture");
+    print_line("- `N`: Start a new log");
+    print_line("- `L`: Pause/resume logging");
+    print_line("- `S`: Save active log as...");
+    print_line("- `I`: Enter input mode (send typed text to the port)");
+    print_line("- `X`: Toggle hex dump display");
+    print_line("- `O`: Select a different port");
+    print_line("- `B`: Select a different baud rate");
+    print_line("- `F`: Set an include/exclude filter for the console or log");
+    print_line("- `D`/`R`: Toggle the DTR/RTS line");
+    print_line("- `K`: Send a BREAK");
+    print_line("- `U`: Send a file to the port, paced or over XMODEM/YMODEM (`Esc` cancels)");
+    print_line("- `H`: Display this help message");
+    if settings.is_multi_port() {
+        print_line(format!("  (`I`, `O` and `B` apply to the main port, {})", settings.ports[0].label));
+    }
+    if settings.replay.is_some() {
+        print_line("- `Space`: Pause/resume the replay");
+        print_line("- `[`/`]`: Seek the replay back/forward 10 seconds");
+    }
+    if tui::is_active() {
+        print_line("");
+        print_line("Full-screen view: `Up`/`Down`/`PgUp`/`PgDn`/`Home` scroll back (capture keeps running),");
+        print_line("`End` follows new output, `/` searches, `F3`/`Shift+F3` find the next/previous match");
+    }
+    print_line("");
+    print_line("In input mode, `Enter` sends the line, `Up`/`Down` recall previous lines,");
+    print_line("`Ctrl+<key>` sends the control character and `Esc` returns to command mode");
+    print_line("");
+    if !settings.macros.is_empty() || !settings.line_sequences.is_empty() {
+        print_line("Macros (`M` followed by a digit runs an M0-M9 binding):");
+        if tui::is_active() {
+            print_line("  (`F3` searches in full-screen mode, so it cannot be bound)");
+        }
+        for bound in &settings.macros {
+            print_line(format!("- `{}`: {}", bound.key, bound.text));
+        }
+        for sequence in &settings.line_sequences {
+            print_line(format!("- `{}`: {} (line sequence, `Esc` cancels)", sequence.key, sequence.name));
+        }
+        print_line("");
+    }
+    if let Ok(filters) = shared_state.filters.lock() {
+        print_line(format!("Console filter: {}", filters.console.describe()));
+        print_line(format!("Log filter: {}", filters.log.describe()));
+        print_line("");
+    }
+    let share_clients = share::client_addresses(shared_state);
+    if !share_clients.is_empty() {
+        let addresses: Vec<String> = share_clients.iter().map(|address| address.to_string()).collect();
+        print_line(format!("Share clients: {}", addresses.join(", ")));
+        print_line("");
+    }
     print_separator();
 }
 
+/// Sends the macro or runs the line sequence bound to the key
+fn run_key_binding(settings: &Settings, shared_state: &State, key: MacroKey) -> Result<()> {
+    if let Some(sequence) = modem::find_sequence(&settings.line_sequences, key) {
+        modem::start_sequence(shared_state, sequence);
+        return Ok(());
+    }
+    let Some(bound) = macros::find(&settings.macros, key) else {
+        print_warning(&format!("No macro or line sequence is bound to {key}"));
+        return Ok(());
+    };
+    serial::write_to_port(shared_state, &bound.bytes)?;
+    print_message(format!("Sent macro {key}: {}", bound.text));
+    Ok(())
+}
+
 fn toggle_pause_capture(shared_state: &State) -> Result<()> {
     let current = shared_state.capture_paused.load(Ordering::Relaxed);
     let new_value = !current;
@@ -107,6 +322,92 @@ fn toggle_pause_capture(shared_state: &State) -> Result<()> {
     Ok(())
 }
 
+fn change_port(settings: &mut Settings, shared_state: &State) -> Result<()> {
+    let port = utils::run_prompt(shared_state, settings::select_port)?;
+    let new_settings = Settings {
+        port,
+        // picking a port ends a replay
+        replay: None,
+        ..settings.clone()
+    };
+    request_reconnect(settings, new_settings, shared_state)
+}
+
+fn change_baud_rate(settings: &mut Settings, shared_state: &State) -> Result<()> {
+    let baud_rate = utils::run_prompt(shared_state, settings::select_baud_rate)?;
+    let new_settings = Settings {
+        baud_rate,
+        ..settings.clone()
+    };
+    request_reconnect(settings, new_settings, shared_state)
+}
+
+fn toggle_replay_pause(shared_state: &State) {
+    let paused = !shared_state.replay_paused.load(Ordering::Relaxed);
+    shared_state.replay_paused.store(paused, Ordering::Relaxed);
+    if paused {
+        print_message(format!("Replay {}", "paused".yellow()));
+    } else {
+        print_message(format!("Replay {}", "resumed".green()));
+    }
+}
+
+fn seek_replay(shared_state: &State, step_ms: i64) {
+    shared_state.replay_seek_ms.fetch_add(step_ms, Ordering::Relaxed);
+}
+
+fn change_filter(shared_state: &State) -> Result<()> {
+    let change = utils::run_prompt(shared_state, settings::select_filter)?;
+    let mut filters = shared_state
+        .filters
+        .lock()
+        .map_err(|e| SpewcapError::Settings(format!("Failed to acquire lock on filters: {e}")))?;
+    filters.apply(change);
+    print_message(format!(
+        "Filters: console {}, log {}",
+        filters.console.describe().cyan(),
+        filters.log.describe().cyan()
+    ));
+    Ok(())
+}
+
+fn request_reconnect(
+    settings: &mut Settings,
+    new_settings: Settings,
+    shared_state: &State,
+) -> Result<()> {
+    let mut pending_settings = shared_state
+        .pending_settings
+        .lock()
+        .map_err(|e| SpewcapError::Settings(format!("Failed to acquire lock on pending settings: {e}")))?;
+    *pending_settings = Some(new_settings.clone());
+    *settings = new_settings;
+    shared_state.reconnect_requested.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+fn toggle_hex_display(shared_state: &State) {
+    let new_value = !shared_state.hex_display.load(Ordering::Relaxed);
+    shared_state.hex_display.store(new_value, Ordering::Relaxed);
+
+    if new_value {
+        print_message(format!("Display mode: {}", "hex".cyan()));
+    } else {
+        print_message(format!("Display mode: {}", "text".cyan()));
+    }
+}
+
+fn toggle_input_mode(shared_state: &State) {
+    let new_value = !shared_state.input_mode.load(Ordering::Relaxed);
+    shared_state.input_mode.store(new_value, Ordering::Relaxed);
+
+    if new_value {
+        print_message(format!("Input mode {} (press `Esc` to leave)", "on".green()));
+    } else {
+        print_message(format!("Input mode {}", "off".yellow()));
+    }
+}
+
 fn toggle_pause_logging(shared_state: &State) -> Result<()> {
     let mut log_state = get_log_state(shared_state)?;
     match log_state.active_log {
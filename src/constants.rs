@@ -1,12 +1,46 @@
+use colored::Color;
 use std::time::Duration;
 
 pub const DEFAULT_BAUD_RATE: u32 = 115200;
+pub const DEFAULT_DATA_BITS: u8 = 8;
+pub const DEFAULT_PARITY: &str = "none";
+pub const DEFAULT_STOP_BITS: u8 = 1;
+pub const DEFAULT_FLOW_CONTROL: &str = "none";
+pub const DEFAULT_LOG_NAME_TEMPLATE: &str = "log_{date}_{time}.txt";
+pub const DEFAULT_DISPLAY_MODE: &str = "text";
+pub const DEFAULT_LOG_FORMAT: &str = "text";
+pub const DEFAULT_DECODING: &str = "lossy";
+pub const DEFAULT_IDLE_FLUSH_MS: u64 = 0;
+pub const DEFAULT_NEWLINE_MODE: &str = "lf";
+pub const DEFAULT_LOG_LINE_ENDING: &str = "keep";
+pub const DEFAULT_TIMESTAMP_MODE: &str = "elapsed";
+pub const DEFAULT_LOCAL_TIMESTAMP_FORMAT: &str = "%H:%M:%S%.3f";
+pub const DEFAULT_UTC_TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.3fZ";
+pub const DEFAULT_HIGHLIGHT_SCOPE: &str = "line";
+pub const DEFAULT_SHARE_MODE: &str = "lines";
+pub const DEFAULT_REPLAY_SPEED: f64 = 1.0;
+pub const DEFAULT_SCROLLBACK_LINES: usize = 10_000;
+/// Label colors given to the ports of a session in order, unless configured
+pub const PORT_LABEL_COLORS: &[Color] = &[
+    Color::Cyan,
+    Color::Green,
+    Color::Magenta,
+    Color::Yellow,
+    Color::Blue,
+    Color::BrightRed,
+];
+pub const DEFAULT_HEX_WIDTH: usize = 16;
+pub const MAX_HEX_WIDTH: usize = 64;
 
 pub const DEFAULT_LINE_BUFFER_CAPACITY: usize = 8192;
 pub const STDOUT_BUFFER_CAPACITY: usize = 1024;
 pub const SERIAL_READ_BUFFER_SIZE: usize = 2048;
+/// Start bit, 8 data bits and a stop bit, used to pace replays without timestamps
+pub const SERIAL_BITS_PER_BYTE: f64 = 10.0;
 pub const LOG_WRITER_BUFFER_CAPACITY: usize = 8192;
 pub const LOG_LINE_BUFFER_INITIAL_CAPACITY: usize = 512;
+/// Highest numeric suffix tried when a new log's file name is already taken
+pub const MAX_LOG_NAME_SUFFIX: usize = 999;
 pub const TIMESTAMP_BUFFER_INITIAL_CAPACITY: usize = 32;
 pub const LINE_BUFFER_SHRINK_THRESHOLD: usize = 2048;
 pub const LINE_BUFFER_SHRINK_TARGET: usize = 512;
@@ -15,19 +49,64 @@ pub const TIMESTAMP_BUFFER_SHRINK_TARGET: usize = 32;
 
 pub const COMMAND_POLL_PERIOD_MS: u64 = 100;
 pub const SERIAL_READ_TIMEOUT_MS: u64 = 100;
+pub const SERIAL_WRITE_TIMEOUT_MS: u64 = 1000;
 pub const SERIAL_NO_DATA_SLEEP_MS: u64 = 10;
 pub const SERIAL_RETRY_DELAY_MS: u64 = 500;
+pub const TCP_CONNECT_TIMEOUT_MS: u64 = 3000;
 pub const SIGNAL_MONITOR_SLEEP_MS: u64 = 100;
+pub const TUI_RATE_INTERVAL_MS: u64 = 1000;
+pub const SHARE_POLL_INTERVAL_MS: u64 = 100;
+pub const REPLAY_MAX_STEP_MS: u64 = 100;
+pub const REPLAY_SEEK_STEP_MS: i64 = 10_000;
+pub const SEND_POLL_INTERVAL_MS: u64 = 10;
+pub const SEND_WAIT_TIMEOUT_MS: u64 = 5000;
+pub const SEND_PROGRESS_INTERVAL_MS: u64 = 2000;
+pub const SEND_DEFAULT_CHAR_DELAY_MS: u64 = 5;
+pub const SEND_DEFAULT_LINE_DELAY_MS: u64 = 100;
+pub const TRANSFER_POLL_INTERVAL_MS: u64 = 100;
+pub const TRANSFER_START_TIMEOUT_MS: u64 = 60_000;
+pub const TRANSFER_REPLY_TIMEOUT_MS: u64 = 10_000;
+pub const MODEM_POLL_INTERVAL_MS: u64 = 200;
+pub const LINE_SEQUENCE_POLL_INTERVAL_MS: u64 = 10;
+pub const BREAK_DURATION_MS: u64 = 250;
 
 pub const COMMAND_POLL_PERIOD: Duration = Duration::from_millis(COMMAND_POLL_PERIOD_MS);
 pub const SERIAL_READ_TIMEOUT: Duration = Duration::from_millis(SERIAL_READ_TIMEOUT_MS);
+pub const SERIAL_WRITE_TIMEOUT: Duration = Duration::from_millis(SERIAL_WRITE_TIMEOUT_MS);
 pub const SERIAL_NO_DATA_SLEEP: Duration = Duration::from_millis(SERIAL_NO_DATA_SLEEP_MS);
 pub const SERIAL_RETRY_DELAY: Duration = Duration::from_millis(SERIAL_RETRY_DELAY_MS);
+pub const TCP_CONNECT_TIMEOUT: Duration = Duration::from_millis(TCP_CONNECT_TIMEOUT_MS);
 pub const SIGNAL_MONITOR_SLEEP: Duration = Duration::from_millis(SIGNAL_MONITOR_SLEEP_MS);
+pub const TUI_RATE_INTERVAL: Duration = Duration::from_millis(TUI_RATE_INTERVAL_MS);
+pub const SHARE_POLL_INTERVAL: Duration = Duration::from_millis(SHARE_POLL_INTERVAL_MS);
+pub const REPLAY_MAX_STEP: Duration = Duration::from_millis(REPLAY_MAX_STEP_MS);
+pub const SEND_POLL_INTERVAL: Duration = Duration::from_millis(SEND_POLL_INTERVAL_MS);
+pub const SEND_WAIT_TIMEOUT: Duration = Duration::from_millis(SEND_WAIT_TIMEOUT_MS);
+pub const SEND_PROGRESS_INTERVAL: Duration = Duration::from_millis(SEND_PROGRESS_INTERVAL_MS);
+pub const TRANSFER_POLL_INTERVAL: Duration = Duration::from_millis(TRANSFER_POLL_INTERVAL_MS);
+pub const TRANSFER_START_TIMEOUT: Duration = Duration::from_millis(TRANSFER_START_TIMEOUT_MS);
+pub const TRANSFER_REPLY_TIMEOUT: Duration = Duration::from_millis(TRANSFER_REPLY_TIMEOUT_MS);
+pub const MODEM_POLL_INTERVAL: Duration = Duration::from_millis(MODEM_POLL_INTERVAL_MS);
+pub const LINE_SEQUENCE_POLL_INTERVAL: Duration = Duration::from_millis(LINE_SEQUENCE_POLL_INTERVAL_MS);
+pub const BREAK_DURATION: Duration = Duration::from_millis(BREAK_DURATION_MS);
 
 pub const HIGH_THROUGHPUT_YIELD_THRESHOLD: usize = 100;
 pub const LOG_FLUSH_INTERVAL: usize = 10;
+pub const SEND_CHUNK_SIZE: usize = 256;
+/// Chunks queued for a share client before it is dropped for not keeping up
+pub const SHARE_QUEUE_LENGTH: usize = 256;
+pub const SEND_TAP_LIMIT: usize = 65536;
+pub const TRANSFER_MAX_RETRIES: usize = 10;
+
+pub const TRANSMIT_LINE_ENDING: &str = "\r";
+pub const INPUT_PROMPT: &str = "> ";
 
 pub const MILLIS_PER_HOUR: u128 = 3_600_000;
 pub const MILLIS_PER_MINUTE: u128 = 60_000;
 pub const MILLIS_PER_SECOND: u128 = 1_000;
+
+pub const EXIT_CODE_PATTERN_MATCHED: i32 = 0;
+pub const EXIT_CODE_ERROR: i32 = 1;
+pub const EXIT_CODE_TIMEOUT: i32 = 2;
+pub const EXIT_CODE_LINE_LIMIT: i32 = 3;
+pub const EXIT_CODE_BYTE_LIMIT: i32 = 4;
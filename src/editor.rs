@@ -0,0 +1,157 @@
+pub struct LineEditor {
+    buffer: Vec<char>,
+    cursor: usize,
+    history: Vec<String>,
+    history_index: Option<usize>,
+}
+
+impl LineEditor {
+    pub fn new() -> Self {
+        LineEditor {
+            buffer: Vec::new(),
+            cursor: 0,
+            history: Vec::new(),
+            history_index: None,
+        }
+    }
+
+    pub fn insert(&mut self, c: char) {
+        self.buffer.insert(self.cursor, c);
+        self.cursor += 1;
+    }
+
+    pub fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            self.buffer.remove(self.cursor);
+        }
+    }
+
+    pub fn delete(&mut self) {
+        if self.cursor < self.buffer.len() {
+            self.buffer.remove(self.cursor);
+        }
+    }
+
+    pub fn move_left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn move_right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.buffer.len());
+    }
+
+    pub fn move_home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn move_end(&mut self) {
+        self.cursor = self.buffer.len();
+    }
+
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+        self.cursor = 0;
+        self.history_index = None;
+    }
+
+    pub fn history_previous(&mut self) {
+        if self.history.is_empty() {
+            return;
+        }
+        let index = match self.history_index {
+            Some(0) => 0,
+            Some(index) => index - 1,
+            None => self.history.len() - 1,
+        };
+        self.recall(index);
+    }
+
+    pub fn history_next(&mut self) {
+        match self.history_index {
+            Some(index) if index + 1 < self.history.len() => self.recall(index + 1),
+            Some(_) => self.clear(),
+            None => {}
+        }
+    }
+
+    /// Returns the edited line and resets the editor, remembering non-empty lines in history
+    pub fn take_line(&mut self) -> String {
+        let line: String = self.buffer.iter().collect();
+        if !line.is_empty() && self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+        }
+        self.clear();
+        line
+    }
+
+    pub fn text(&self) -> String {
+        self.buffer.iter().collect()
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    fn recall(&mut self, index: usize) {
+        self.buffer = self.history[index].chars().collect();
+        self.cursor = self.buffer.len();
+        self.history_index = Some(index);
+    }
+}
+
+impl Default for LineEditor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_str(editor: &mut LineEditor, text: &str) {
+        text.chars().for_each(|c| editor.insert(c));
+    }
+
+    #[test]
+    fn test_editor_insert_and_take() {
+        let mut editor = LineEditor::new();
+        type_str(&mut editor, "help");
+        assert_eq!(editor.text(), "help");
+        assert_eq!(editor.take_line(), "help");
+        assert_eq!(editor.text(), "");
+        assert_eq!(editor.cursor(), 0);
+    }
+
+    #[test]
+    fn test_editor_cursor_editing() {
+        let mut editor = LineEditor::new();
+        type_str(&mut editor, "bot");
+        editor.move_left();
+        editor.insert('o');
+        assert_eq!(editor.text(), "boot");
+        editor.move_home();
+        editor.delete();
+        editor.move_end();
+        editor.backspace();
+        assert_eq!(editor.text(), "oo");
+    }
+
+    #[test]
+    fn test_editor_history() {
+        let mut editor = LineEditor::new();
+        type_str(&mut editor, "first");
+        editor.take_line();
+        type_str(&mut editor, "second");
+        editor.take_line();
+        editor.history_previous();
+        assert_eq!(editor.text(), "second");
+        editor.history_previous();
+        assert_eq!(editor.text(), "first");
+        editor.history_next();
+        assert_eq!(editor.text(), "second");
+        editor.history_next();
+        assert_eq!(editor.text(), "");
+    }
+}
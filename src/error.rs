@@ -32,18 +32,111 @@ pub enum SpewcapError {
     #[error("Invalid port: {0}")]
     InvalidPort(String),
     
+    #[error("Invalid data bits: {0} (expected 5, 6, 7 or 8)")]
+    InvalidDataBits(u8),
+    
+    #[error("Invalid parity: {0} (expected none, odd or even)")]
+    InvalidParity(String),
+    
+    #[error("Invalid stop bits: {0} (expected 1 or 2)")]
+    InvalidStopBits(u8),
+    
+    #[error("Invalid flow control: {0} (expected none, software or hardware)")]
+    InvalidFlowControl(String),
+    
+    #[error("Invalid line level: {0} (expected on or off)")]
+    InvalidLineLevel(String),
+    
     #[error("Invalid file path: {0}")]
     InvalidFilePath(String),
     
+    #[error("Invalid log name template: {0}")]
+    InvalidLogNameTemplate(String),
+    
+    #[error("Invalid display mode: {0} (expected text or hex)")]
+    InvalidDisplayMode(String),
+    
+    #[error("Invalid log format: {0} (expected text, hex or raw)")]
+    InvalidLogFormat(String),
+    
+    #[error("Invalid hex width: {0} (expected 1 to 64 bytes)")]
+    InvalidHexWidth(usize),
+    
+    #[error("Invalid decoding: {0} (expected lossy, escaped, latin1, cp437 or raw)")]
+    InvalidDecoding(String),
+    
+    #[error("Invalid newline mode: {0} (expected lf, cr, crlf or any)")]
+    InvalidNewlineMode(String),
+    
+    #[error("Invalid log line ending: {0} (expected keep, lf or crlf)")]
+    InvalidLineEnding(String),
+    
+    #[error("Invalid timestamp mode: {0} (expected elapsed, local, utc or delta)")]
+    InvalidTimestampMode(String),
+    
+    #[error("Invalid timestamp format: {0}")]
+    InvalidTimestampFormat(String),
+    
+    #[error("Invalid regex: {0}")]
+    InvalidRegex(String),
+    
+    #[error("Invalid capture limit: {0}")]
+    InvalidLimit(String),
+    
+    #[error("Invalid color: {0} (expected eg. red, bright blue)")]
+    InvalidColor(String),
+    
+    #[error("Invalid highlight scope: {0} (expected line or match)")]
+    InvalidHighlightScope(String),
+    
+    #[error("Invalid share address: {0} (expected a port or ip:port)")]
+    InvalidShareAddress(String),
+    
+    #[error("Invalid share mode: {0} (expected lines or raw)")]
+    InvalidShareMode(String),
+    
+    #[error("Invalid replay speed: {0} (expected a positive factor, or 0 for as fast as possible)")]
+    InvalidReplaySpeed(f64),
+    
+    #[error("Invalid port label: {0}")]
+    InvalidPortLabel(String),
+    
+    #[error("Invalid extra port: {0} (expected [LABEL=]PORT[@BAUD])")]
+    InvalidPortSpec(String),
+    
+    #[error("Invalid macro key: {0} (expected F1 to F12, or M0 to M9)")]
+    InvalidMacroKey(String),
+    
+    #[error("Invalid macro: {0}")]
+    InvalidMacro(String),
+    
+    #[error("Invalid line sequence: {0}")]
+    InvalidLineSequence(String),
+    
+    #[error("Line control error: {0}")]
+    LineControl(String),
+    
     #[error("Log error: {0}")]
     Log(String),
     
     #[error("Signal handling error: {0}")]
     Signal(String),
     
+    #[error("Transmit error: {0}")]
+    Transmit(String),
+    
+    #[error("Transfer error: {0}")]
+    Transfer(String),
+    
     #[error("No serial ports found")]
     NoPortsFound,
     
+    #[error("Serial port is not connected")]
+    PortNotConnected,
+    
+    #[error("Connection error: {0}")]
+    Connection(String),
+    
     // #[error("User cancelled operation")]
     // UserCancelled,
 }
@@ -0,0 +1,107 @@
+use regex::Regex;
+
+/// Include/exclude patterns deciding which received lines are kept
+#[derive(Clone, Debug, Default)]
+pub struct LineFilter {
+    pub include: Option<Regex>,
+    pub exclude: Option<Regex>,
+}
+
+impl LineFilter {
+    pub fn allows(&self, text: &str) -> bool {
+        let included = self.include.as_ref().is_none_or(|pattern| pattern.is_match(text));
+        let excluded = self.exclude.as_ref().is_some_and(|pattern| pattern.is_match(text));
+        included && !excluded
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.include.is_some() || self.exclude.is_some()
+    }
+
+    pub fn describe(&self) -> String {
+        match (&self.include, &self.exclude) {
+            (None, None) => "none".to_string(),
+            (Some(include), None) => format!("include /{include}/"),
+            (None, Some(exclude)) => format!("exclude /{exclude}/"),
+            (Some(include), Some(exclude)) => format!("include /{include}/, exclude /{exclude}/"),
+        }
+    }
+}
+
+/// Separate filters for the console and the log, so chatter can be hidden on screen but still logged
+#[derive(Clone, Debug, Default)]
+pub struct Filters {
+    pub console: LineFilter,
+    pub log: LineFilter,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FilterTarget {
+    Console,
+    Log,
+    Both,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FilterKind {
+    Include,
+    Exclude,
+}
+
+/// A filter edit made from the key command prompt; `pattern: None` clears the filter
+pub struct FilterChange {
+    pub target: FilterTarget,
+    pub kind: FilterKind,
+    pub pattern: Option<Regex>,
+}
+
+impl Filters {
+    pub fn apply(&mut self, change: FilterChange) {
+        let filters = match change.target {
+            FilterTarget::Console => vec![&mut self.console],
+            FilterTarget::Log => vec![&mut self.log],
+            FilterTarget::Both => vec![&mut self.console, &mut self.log],
+        };
+        for filter in filters {
+            let slot = match change.kind {
+                FilterKind::Include => &mut filter.include,
+                FilterKind::Exclude => &mut filter.exclude,
+            };
+            *slot = change.pattern.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_filter_allows() {
+        let filter = LineFilter {
+            include: Some(Regex::new("^\\[app\\]").unwrap()),
+            exclude: Some(Regex::new("heartbeat").unwrap()),
+        };
+        assert!(filter.allows("[app] started\n"));
+        assert!(!filter.allows("[app] heartbeat\n"));
+        assert!(!filter.allows("[wifi] scan\n"));
+        assert!(LineFilter::default().allows("anything\n"));
+    }
+
+    #[test]
+    fn test_filters_apply_change() {
+        let mut filters = Filters::default();
+        filters.apply(FilterChange {
+            target: FilterTarget::Both,
+            kind: FilterKind::Exclude,
+            pattern: Some(Regex::new("noise").unwrap()),
+        });
+        filters.apply(FilterChange {
+            target: FilterTarget::Log,
+            kind: FilterKind::Exclude,
+            pattern: None,
+        });
+        assert_eq!(filters.console.describe(), "exclude /noise/");
+        assert_eq!(filters.log.describe(), "none");
+    }
+}
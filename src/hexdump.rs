@@ -0,0 +1,88 @@
+use std::fmt::Write as FmtWrite;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DisplayMode {
+    Text,
+    Hex,
+}
+
+/// Renders received bytes as classic offset/hex/ASCII rows, keeping the offset across chunks
+pub struct HexDumper {
+    offset: u64,
+    width: usize,
+}
+
+impl HexDumper {
+    pub fn new(width: usize) -> Self {
+        HexDumper { offset: 0, width }
+    }
+
+    /// Advances the offset without rendering, so offsets stay correct while the dump is hidden
+    pub fn skip(&mut self, count: usize) {
+        self.offset += count as u64;
+    }
+
+    pub fn dump(&mut self, data: &[u8]) -> Vec<String> {
+        data.chunks(self.width)
+            .map(|row| {
+                let line = self.format_row(row);
+                self.offset += row.len() as u64;
+                line
+            })
+            .collect()
+    }
+
+    fn format_row(&self, row: &[u8]) -> String {
+        let mut line = String::with_capacity(self.width * 4 + 16);
+        let _ = write!(line, "{:08x}  ", self.offset);
+        for index in 0..self.width {
+            match row.get(index) {
+                Some(byte) => {
+                    let _ = write!(line, "{:02x} ", byte);
+                }
+                None => line.push_str("   "),
+            }
+            if index % 8 == 7 && index + 1 < self.width {
+                line.push(' ');
+            }
+        }
+        line.push_str(" |");
+        line.extend(row.iter().map(|&byte| printable(byte)));
+        line.push_str("|\n");
+        line
+    }
+}
+
+fn printable(byte: u8) -> char {
+    if byte.is_ascii_graphic() || byte == b' ' {
+        byte as char
+    } else {
+        '.'
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dump_full_row() {
+        let mut dumper = HexDumper::new(16);
+        let rows = dumper.dump(b"Hello, world!\r\n\x00");
+        assert_eq!(
+            rows,
+            vec!["00000000  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 0d 0a 00  |Hello, world!...|\n"]
+        );
+    }
+
+    #[test]
+    fn test_dump_partial_row_keeps_offset() {
+        let mut dumper = HexDumper::new(8);
+        let rows = dumper.dump(b"0123456789");
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1], "00000008  38 39                    |89|\n");
+        dumper.skip(6);
+        let rows = dumper.dump(b"A");
+        assert!(rows[0].starts_with("00000010  41"));
+    }
+}
@@ -0,0 +1,118 @@
+use colored::{Color, ColoredString, Colorize};
+use regex::Regex;
+
+/// How much of a matching line a highlight rule colors
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HighlightScope {
+    Line,
+    Match,
+}
+
+#[derive(Clone, Debug)]
+pub struct HighlightRule {
+    pub pattern: Regex,
+    pub foreground: Option<Color>,
+    pub background: Option<Color>,
+    pub bold: bool,
+    pub scope: HighlightScope,
+}
+
+impl HighlightRule {
+    fn style(&self, text: &str) -> ColoredString {
+        let mut styled = text.normal();
+        if let Some(color) = self.foreground {
+            styled = styled.color(color);
+        }
+        if let Some(color) = self.background {
+            styled = styled.on_color(color);
+        }
+        if self.bold {
+            styled = styled.bold();
+        }
+        styled
+    }
+}
+
+/// Returns the line with the highlight rules applied, or `None` if no rule matches.
+/// The first matching whole-line rule wins; otherwise every match-only rule colors its
+/// matches, with earlier rules taking precedence where matches overlap.
+pub fn highlight_line(rules: &[HighlightRule], text: &str) -> Option<String> {
+    if rules.is_empty() {
+        return None;
+    }
+    // keep the line ending outside the styling so background colors do not spill over
+    let content = text.trim_end_matches(['\r', '\n']);
+    let ending = &text[content.len()..];
+    if let Some(rule) = rules
+        .iter()
+        .find(|rule| rule.scope == HighlightScope::Line && rule.pattern.is_match(content))
+    {
+        return Some(format!("{}{}", rule.style(content), ending));
+    }
+    let spans = match_spans(rules, content);
+    if spans.is_empty() {
+        return None;
+    }
+    let mut highlighted = String::with_capacity(text.len() + spans.len() * 16);
+    let mut position = 0;
+    for (start, end, rule) in spans {
+        highlighted.push_str(&content[position..start]);
+        highlighted.push_str(&rule.style(&content[start..end]).to_string());
+        position = end;
+    }
+    highlighted.push_str(&content[position..]);
+    highlighted.push_str(ending);
+    Some(highlighted)
+}
+
+fn match_spans<'a>(rules: &'a [HighlightRule], content: &str) -> Vec<(usize, usize, &'a HighlightRule)> {
+    let mut spans: Vec<(usize, usize, &HighlightRule)> = Vec::new();
+    for rule in rules.iter().filter(|rule| rule.scope == HighlightScope::Match) {
+        for found in rule.pattern.find_iter(content) {
+            let (start, end) = (found.start(), found.end());
+            let overlaps = spans.iter().any(|&(s, e, _)| start < e && s < end);
+            if start < end && !overlaps {
+                spans.push((start, end, rule));
+            }
+        }
+    }
+    spans.sort_by_key(|&(start, _, _)| start);
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::override_colors;
+
+    fn rule(pattern: &str, foreground: Color, scope: HighlightScope) -> HighlightRule {
+        HighlightRule {
+            pattern: Regex::new(pattern).unwrap(),
+            foreground: Some(foreground),
+            background: None,
+            bold: false,
+            scope,
+        }
+    }
+
+    #[test]
+    fn test_highlight_whole_line() {
+        let _colors = override_colors(true);
+        let rules = [rule("panic", Color::Red, HighlightScope::Line)];
+        let line = highlight_line(&rules, "kernel panic\r\n").unwrap();
+        assert_eq!(line, "\x1b[31mkernel panic\x1b[0m\r\n");
+        assert!(highlight_line(&rules, "booting\n").is_none());
+    }
+
+    #[test]
+    fn test_highlight_matches_only() {
+        let _colors = override_colors(true);
+        let rules = [
+            rule("ERROR", Color::Red, HighlightScope::Match),
+            rule("ERR", Color::Yellow, HighlightScope::Match),
+            rule(r"\d+", Color::Cyan, HighlightScope::Match),
+        ];
+        let line = highlight_line(&rules, "ERROR 42\n").unwrap();
+        assert_eq!(line, "\x1b[31mERROR\x1b[0m \x1b[36m42\x1b[0m\n");
+    }
+}
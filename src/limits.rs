@@ -0,0 +1,166 @@
+use regex::Regex;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::constants::*;
+use crate::settings::Settings;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    PatternMatched,
+    Timeout,
+    LineLimit,
+    ByteLimit,
+}
+
+impl StopReason {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            StopReason::PatternMatched => EXIT_CODE_PATTERN_MATCHED,
+            StopReason::Timeout => EXIT_CODE_TIMEOUT,
+            StopReason::LineLimit => EXIT_CODE_LINE_LIMIT,
+            StopReason::ByteLimit => EXIT_CODE_BYTE_LIMIT,
+        }
+    }
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopReason::PatternMatched => write!(f, "stop pattern matched"),
+            StopReason::Timeout => write!(f, "capture duration elapsed"),
+            StopReason::LineLimit => write!(f, "line limit reached"),
+            StopReason::ByteLimit => write!(f, "byte limit reached"),
+        }
+    }
+}
+
+/// Tracks received data against the configured stop conditions
+pub struct CaptureLimits {
+    deadline: Option<Instant>,
+    max_lines: Option<u64>,
+    max_bytes: Option<u64>,
+    stop_pattern: Option<Regex>,
+    lines: u64,
+    bytes: u64,
+    // start of the current line, already shown as partials before its ending arrived
+    partial: String,
+}
+
+impl CaptureLimits {
+    pub fn new(settings: &Settings) -> Self {
+        Self::with_limits(
+            settings.stop_after,
+            settings.max_lines,
+            settings.max_bytes,
+            settings.stop_pattern.clone(),
+        )
+    }
+
+    pub fn with_limits(
+        stop_after: Option<Duration>,
+        max_lines: Option<u64>,
+        max_bytes: Option<u64>,
+        stop_pattern: Option<Regex>,
+    ) -> Self {
+        CaptureLimits {
+            deadline: stop_after.map(|duration| Instant::now() + duration),
+            max_lines,
+            max_bytes,
+            stop_pattern,
+            lines: 0,
+            bytes: 0,
+            partial: String::new(),
+        }
+    }
+
+    pub fn check_timeout(&self) -> Option<StopReason> {
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => Some(StopReason::Timeout),
+            _ => None,
+        }
+    }
+
+    pub fn record_bytes(&mut self, count: usize) -> Option<StopReason> {
+        self.bytes += count as u64;
+        match self.max_bytes {
+            Some(max_bytes) if self.bytes >= max_bytes => Some(StopReason::ByteLimit),
+            _ => None,
+        }
+    }
+
+    /// Counts a complete line and checks it, along with any partials it continues
+    pub fn record_line(&mut self, line: &str) -> Option<StopReason> {
+        self.lines += 1;
+        let matched = if self.partial.is_empty() {
+            self.matches(line)
+        } else {
+            self.partial.push_str(line);
+            let matched = self.matches(&self.partial);
+            self.partial.clear();
+            matched
+        };
+        if matched {
+            return Some(StopReason::PatternMatched);
+        }
+        match self.max_lines {
+            Some(max_lines) if self.lines >= max_lines => Some(StopReason::LineLimit),
+            _ => None,
+        }
+    }
+
+    /// Checks a partial line (eg. a prompt flushed after an idle timeout) without counting it.
+    /// The line so far is kept so a pattern spanning the flush still matches once it ends.
+    pub fn record_partial(&mut self, text: &str) -> Option<StopReason> {
+        self.partial.push_str(text);
+        self.matches(&self.partial).then_some(StopReason::PatternMatched)
+    }
+
+    fn matches(&self, text: &str) -> bool {
+        self.stop_pattern.as_ref().is_some_and(|pattern| pattern.is_match(text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_limit() {
+        let mut limits = CaptureLimits::with_limits(None, Some(2), None, None);
+        assert_eq!(limits.record_line("one\n"), None);
+        assert_eq!(limits.record_line("two\n"), Some(StopReason::LineLimit));
+    }
+
+    #[test]
+    fn test_byte_limit() {
+        let mut limits = CaptureLimits::with_limits(None, None, Some(10), None);
+        assert_eq!(limits.record_bytes(6), None);
+        assert_eq!(limits.record_bytes(6), Some(StopReason::ByteLimit));
+    }
+
+    #[test]
+    fn test_stop_pattern_takes_precedence() {
+        let pattern = Regex::new("login:").unwrap();
+        let mut limits = CaptureLimits::with_limits(None, Some(1), None, Some(pattern));
+        assert_eq!(limits.record_line("buildroot login: \n"), Some(StopReason::PatternMatched));
+    }
+
+    #[test]
+    fn test_stop_pattern_spans_partial() {
+        let pattern = Regex::new("Hit any key").unwrap();
+        let mut limits = CaptureLimits::with_limits(None, None, None, Some(pattern));
+        assert_eq!(limits.record_partial("Hit any"), None);
+        assert_eq!(limits.record_line(" key to stop autoboot\n"), Some(StopReason::PatternMatched));
+        assert_eq!(limits.record_line("Hit any key\n"), Some(StopReason::PatternMatched));
+        assert_eq!(limits.record_line(" key\n"), None);
+    }
+
+    #[test]
+    fn test_timeout() {
+        let limits = CaptureLimits::with_limits(Some(Duration::ZERO), None, None, None);
+        assert_eq!(limits.check_timeout(), Some(StopReason::Timeout));
+        let limits = CaptureLimits::with_limits(None, None, None, None);
+        assert_eq!(limits.check_timeout(), None);
+    }
+}
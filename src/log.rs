@@ -1,18 +1,43 @@
 use chrono::Local;
 use colored::Colorize;
 use regex::Regex;
-use std::fmt::Write as FmtWrite;
-use std::fs::copy;
-use std::io::Write;
-use std::path::PathBuf;
-use std::time::{Duration, Instant};
+use std::fs::{copy, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
 use std::{fs::File, io::BufWriter};
 
 use crate::constants::*;
+use crate::settings::Settings;
+use crate::timestamp::{Moment, Timestamper};
 use crate::utils::{ansi_regex, print_message};
 use crate::validation;
 use crate::error::Result;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+    Hex,
+    Raw,
+}
+
+/// Line ending written to the log for each received line
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineEnding {
+    Keep,
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    fn as_str(&self) -> Option<&'static str> {
+        match self {
+            LineEnding::Keep => None,
+            LineEnding::Lf => Some("\n"),
+            LineEnding::CrLf => Some("\r\n"),
+        }
+    }
+}
+
 pub struct LogFile {
     inner: Log,
     temp_file_path: PathBuf,
@@ -20,8 +45,12 @@ pub struct LogFile {
 }
 
 impl LogFile {
-    pub fn new(prepend_timestamps: bool) -> std::result::Result<Self, std::io::Error> {
-        let inner = Log::new(prepend_timestamps)?;
+    pub fn new(
+        file_path: PathBuf,
+        settings: &Settings,
+        start: Moment,
+    ) -> std::result::Result<Self, std::io::Error> {
+        let inner = Log::new(file_path, settings, start)?;
         let temp_file_path = inner.file_path.clone();
         Ok(LogFile {
             inner,
@@ -34,6 +63,10 @@ impl LogFile {
         self.cleanup_on_drop = false;
     }
 
+    pub fn is_kept(&self) -> bool {
+        !self.cleanup_on_drop
+    }
+
     pub fn cleanup_temp_file(&mut self) -> std::io::Result<()> {
         if self.cleanup_on_drop && self.temp_file_path.exists() {
             self.inner.force_flush()?;
@@ -101,8 +134,10 @@ pub struct Log {
     enabled: bool,
     unsaved_changes: bool,
     prepend_timestamps: bool,
+    line_ending: LineEnding,
+    at_line_start: bool,
     ansi_regex: Regex,
-    start_time: Instant,
+    timestamper: Timestamper,
     // performance optimizations
     timestamp_buffer: String,
     line_buffer: String,
@@ -110,22 +145,27 @@ pub struct Log {
 }
 
 impl Log {
-    pub fn new(prepend_timestamps: bool) -> std::result::Result<Self, std::io::Error> {
-        let filename = format!("log_{}.txt", Local::now().format("%Y%m%d_%H%M%S"));
-        let file_path = PathBuf::from(&filename);
-        let file = File::create(&file_path)?;
+    /// Creates the log at `file_path`, or next to it with a numeric suffix if that file exists
+    pub fn new(file_path: PathBuf, settings: &Settings, start: Moment) -> std::result::Result<Self, std::io::Error> {
+        let (file, file_path) = create_unique(&file_path)?;
+        let filename = file_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
         let writer = BufWriter::with_capacity(LOG_WRITER_BUFFER_CAPACITY, file);
         let ansi_regex = ansi_regex();
-        let start_time = Instant::now();
+        let timestamper = Timestamper::new(settings.timestamp_mode, settings.timestamp_format.clone(), start);
         Ok(Log {
             writer,
             filename,
             file_path,
             enabled: true,
             unsaved_changes: false,
-            prepend_timestamps,
+            prepend_timestamps: settings.timestamps,
+            line_ending: settings.log_line_ending,
+            at_line_start: true,
             ansi_regex: ansi_regex.clone(),
-            start_time,
+            timestamper,
             timestamp_buffer: String::with_capacity(TIMESTAMP_BUFFER_INITIAL_CAPACITY),
             line_buffer: String::with_capacity(LOG_LINE_BUFFER_INITIAL_CAPACITY),
             flush_counter: 0,
@@ -153,7 +193,12 @@ impl Log {
         &self.filename
     }
 
-    pub fn write_line(&mut self, raw_line: &str) -> std::io::Result<()> {
+    pub fn get_file_path(&self) -> &Path {
+        &self.file_path
+    }
+
+    /// Writes a received line; `received_at` is the arrival time used for its timestamp
+    pub fn write_line(&mut self, raw_line: &str, received_at: Moment) -> std::io::Result<()> {
         self.line_buffer.clear();
         // avoid regex if no ANSI codes detected (performance optimization)
         if raw_line.contains('\x1b') {
@@ -162,18 +207,21 @@ impl Log {
         } else {
             self.line_buffer.push_str(raw_line);
         }
-        if self.prepend_timestamps {
-            if let Err(e) = self.create_timestamp_in_buffer(self.start_time.elapsed()) {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    format!("Failed to write timestamp: {}", e)
-                ));
+        if let Some(ending) = self.line_ending.as_str() {
+            normalize_line_ending(&mut self.line_buffer, ending);
+        }
+        // a line continuing an earlier partial write already has its timestamp
+        if self.prepend_timestamps && self.at_line_start {
+            self.timestamp_buffer.clear();
+            if let Err(e) = self.timestamper.write_timestamp(&mut self.timestamp_buffer, received_at) {
+                return Err(std::io::Error::other(format!("Failed to write timestamp: {}", e)));
             }
             self.line_buffer
                 .insert_str(0, &format!("[{}] ", self.timestamp_buffer));
         }
         self.writer
             .write_all(self.line_buffer.as_bytes())?;
+        self.at_line_start = raw_line.ends_with(['\n', '\r']);
         self.unsaved_changes = true;
         self.flush_counter += 1;
         // batch flush (performance optimization)
@@ -191,6 +239,18 @@ impl Log {
         Ok(())
     }
 
+    /// Writes received bytes unmodified (no timestamps or ANSI stripping)
+    pub fn write_raw(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.writer.write_all(data)?;
+        self.unsaved_changes = true;
+        self.flush_counter += 1;
+        if self.flush_counter >= LOG_FLUSH_INTERVAL {
+            let _ = self.writer.flush();
+            self.flush_counter = 0;
+        }
+        Ok(())
+    }
+
     pub fn force_flush(&mut self) -> std::io::Result<()> {
         self.flush_counter = 0;
         self.writer.flush()
@@ -199,6 +259,12 @@ impl Log {
     pub fn save_as(&mut self, new_file_path: &PathBuf) -> Result<()> {
         let path_str = new_file_path.to_string_lossy();
         validation::validate_file_path(&path_str)?;
+        if is_same_file(&self.file_path, new_file_path) {
+            // the working log already lives at the requested path
+            self.unsaved_changes = false;
+            println!("Saved to {}", new_file_path.display());
+            return Ok(());
+        }
         match copy(&self.file_path, new_file_path) {
             Ok(_) => {
                 self.unsaved_changes = false;
@@ -211,18 +277,165 @@ impl Log {
             }
         }
     }
+}
+
+/// Replaces the ending of a terminated line; partial lines are left alone
+fn normalize_line_ending(line: &mut String, ending: &str) {
+    let content_len = line.trim_end_matches(['\r', '\n']).len();
+    if content_len < line.len() {
+        line.truncate(content_len);
+        line.push_str(ending);
+    }
+}
+
+/// Opens a file that did not exist before, so a new log never truncates an earlier one
+/// (or the working file of a log it replaces), eg. "log.txt" -> "log_1.txt"
+fn create_unique(path: &Path) -> std::io::Result<(File, PathBuf)> {
+    for suffix in 0..=MAX_LOG_NAME_SUFFIX {
+        let candidate = match suffix {
+            0 => path.to_path_buf(),
+            suffix => port_log_path(path, &suffix.to_string()),
+        };
+        match OpenOptions::new().write(true).create_new(true).open(&candidate) {
+            Ok(file) => return Ok((file, candidate)),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Err(std::io::Error::new(
+        ErrorKind::AlreadyExists,
+        format!("{} and its numbered alternatives already exist", path.display()),
+    ))
+}
+
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Builds the path of a new working log from the configured folder and name template
+pub fn log_file_path(settings: &Settings, session: usize) -> PathBuf {
+    let filename = expand_log_name(&settings.log_name_template, &settings.port, settings.baud_rate, session);
+    match &settings.log_folder {
+        Some(folder) => folder.join(filename),
+        None => PathBuf::from(filename),
+    }
+}
+
+pub fn expand_log_name(template: &str, port: &str, baud_rate: u32, session: usize) -> String {
+    let now = Local::now();
+    template
+        .replace("{port}", &sanitize_port_name(port))
+        .replace("{baud}", &baud_rate.to_string())
+        .replace("{date}", &now.format("%Y%m%d").to_string())
+        .replace("{time}", &now.format("%H%M%S").to_string())
+        .replace("{session}", &session.to_string())
+}
+
+/// The per-port file next to a merged log, eg. "log_1.txt" -> "log_1_modem.txt"
+pub fn port_log_path(merged_path: &Path, label: &str) -> PathBuf {
+    let stem = merged_path.file_stem().map(|stem| stem.to_string_lossy()).unwrap_or_default();
+    let filename = match merged_path.extension() {
+        Some(extension) => format!("{stem}_{label}.{}", extension.to_string_lossy()),
+        None => format!("{stem}_{label}"),
+    };
+    merged_path.with_file_name(filename)
+}
+
+/// Reduces a port name like "/dev/ttyUSB0" or `\\.\COM10` to something usable in a file name
+pub fn sanitize_port_name(port: &str) -> String {
+    let name = port.rsplit(['/', '\\']).next().unwrap_or(port);
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_log_name() {
+        let name = expand_log_name("{port}_{baud}_{session}.txt", "/dev/ttyUSB0", 115200, 3);
+        assert_eq!(name, "ttyUSB0_115200_3.txt");
+    }
+
+    #[test]
+    fn test_expand_log_name_date_time() {
+        let name = expand_log_name("log_{date}_{time}.txt", "COM3", 9600, 1);
+        assert_eq!(name.len(), "log_YYYYMMDD_HHMMSS.txt".len());
+        assert!(!name.contains('{'));
+    }
+
+    #[test]
+    fn test_normalize_line_ending() {
+        let mut line = String::from("progress 10%\r");
+        normalize_line_ending(&mut line, "\n");
+        assert_eq!(line, "progress 10%\n");
+        let mut line = String::from("ok\n");
+        normalize_line_ending(&mut line, "\r\n");
+        assert_eq!(line, "ok\r\n");
+        let mut line = String::from("login: ");
+        normalize_line_ending(&mut line, "\n");
+        assert_eq!(line, "login: ");
+    }
+
+    #[test]
+    fn test_new_log_keeps_existing_file() {
+        let folder = std::env::temp_dir().join(format!("spewcap_log_test_{}", std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+        let mut settings = Settings::for_tests("COM3");
+        settings.log_folder = Some(folder.clone());
+        settings.log_name_template = "capture.txt".to_string();
+        settings.headless = true;
+        let state = crate::state::init_state();
+
+        crate::utils::start_new_log(&settings, &state).unwrap();
+        let first_path = {
+            let mut log_state = state.log_state.lock().unwrap();
+            let log = log_state.active_log.as_mut().unwrap();
+            log.write_line("first session\n", Moment::now()).unwrap();
+            log.ensure_flushed().unwrap();
+            log.get_file_path().to_path_buf()
+        };
+        crate::utils::start_new_log(&settings, &state).unwrap();
+        let second_path = state.log_state.lock().unwrap().active_log.as_ref().unwrap().get_file_path().to_path_buf();
+
+        assert_eq!(first_path, folder.join("capture.txt"));
+        assert_eq!(second_path, folder.join("capture_1.txt"));
+        assert_eq!(std::fs::read_to_string(&first_path).unwrap(), "first session\n");
+        assert!(second_path.exists());
+        drop(state);
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn test_create_unique_tries_the_last_suffix() {
+        let folder = std::env::temp_dir().join(format!("spewcap_unique_test_{}", std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+        let path = folder.join("capture.txt");
+        File::create(&path).unwrap();
+        for suffix in 1..MAX_LOG_NAME_SUFFIX {
+            File::create(port_log_path(&path, &suffix.to_string())).unwrap();
+        }
+        let (_, last) = create_unique(&path).unwrap();
+        assert_eq!(last, port_log_path(&path, &MAX_LOG_NAME_SUFFIX.to_string()));
+        assert!(create_unique(&path).is_err());
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn test_port_log_path() {
+        assert_eq!(port_log_path(Path::new("logs/boot.txt"), "modem"), PathBuf::from("logs/boot_modem.txt"));
+        assert_eq!(port_log_path(Path::new("capture"), "app"), PathBuf::from("capture_app"));
+    }
 
-    fn create_timestamp_in_buffer(&mut self, duration: Duration) -> std::fmt::Result {
-        self.timestamp_buffer.clear();
-        let total_millis = duration.as_millis();
-        let hours = total_millis / MILLIS_PER_HOUR;
-        let minutes = (total_millis % MILLIS_PER_HOUR) / MILLIS_PER_MINUTE;
-        let seconds = (total_millis % MILLIS_PER_MINUTE) / MILLIS_PER_SECOND;
-        let millis = total_millis % MILLIS_PER_SECOND;
-        write!(
-            self.timestamp_buffer,
-            "{:02}:{:02}:{:02}:{:03}ms",
-            hours, minutes, seconds, millis
-        )
+    #[test]
+    fn test_sanitize_port_name() {
+        assert_eq!(sanitize_port_name("COM3"), "COM3");
+        assert_eq!(sanitize_port_name(r"\\.\COM10"), "COM10");
+        assert_eq!(sanitize_port_name("/dev/tty.usbserial-1410"), "tty_usbserial-1410");
     }
 }
@@ -0,0 +1,104 @@
+use std::fmt;
+
+/// Key a macro is bound to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MacroKey {
+    /// `F1` to `F12`
+    Function(u8),
+    /// `M` followed by a digit
+    Prefixed(char),
+}
+
+impl MacroKey {
+    /// Parses a `[macros]` key name such as "F5" or "M1"
+    pub fn parse(name: &str) -> Option<Self> {
+        let name = name.trim().to_ascii_uppercase();
+        if let Some(number) = name.strip_prefix('F') {
+            let number = number.parse::<u8>().ok()?;
+            return (1..=12).contains(&number).then_some(MacroKey::Function(number));
+        }
+        let mut chars = name.strip_prefix('M')?.chars();
+        match (chars.next(), chars.next()) {
+            (Some(digit), None) if digit.is_ascii_digit() => Some(MacroKey::Prefixed(digit)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for MacroKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MacroKey::Function(number) => write!(f, "F{number}"),
+            MacroKey::Prefixed(digit) => write!(f, "M{digit}"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Macro {
+    pub key: MacroKey,
+    /// The string as written in the config, shown when the macro is sent
+    pub text: String,
+    pub bytes: Vec<u8>,
+}
+
+pub fn find(macros: &[Macro], key: MacroKey) -> Option<&Macro> {
+    macros.iter().find(|m| m.key == key)
+}
+
+/// Turns `\r`, `\n`, `\t`, `\e`, `\0`, `\\` and `\xHH` into the bytes they stand for
+pub fn unescape(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buffer = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+            continue;
+        }
+        let byte = match chars.next() {
+            Some('r') => b'\r',
+            Some('n') => b'\n',
+            Some('t') => b'\t',
+            Some('e') => 0x1b,
+            Some('0') => 0x00,
+            Some('\\') => b'\\',
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                if hex.len() != 2 {
+                    return Err(format!("\\x needs two hex digits in {text}"));
+                }
+                u8::from_str_radix(&hex, 16).map_err(|_| format!("\\x{hex} is not a hex byte in {text}"))?
+            }
+            Some(other) => return Err(format!("unknown escape \\{other} in {text}")),
+            None => return Err(format!("{text} ends with a lone \\")),
+        };
+        bytes.push(byte);
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_macro_key() {
+        assert_eq!(MacroKey::parse("F5"), Some(MacroKey::Function(5)));
+        assert_eq!(MacroKey::parse("f12"), Some(MacroKey::Function(12)));
+        assert_eq!(MacroKey::parse("M0"), Some(MacroKey::Prefixed('0')));
+        assert_eq!(MacroKey::parse("F13"), None);
+        assert_eq!(MacroKey::parse("M10"), None);
+        assert_eq!(MacroKey::parse("ctrl+r"), None);
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape(r"reset\r").unwrap(), b"reset\r");
+        assert_eq!(unescape(r"\x03\xFF\\").unwrap(), vec![0x03, 0xff, b'\\']);
+        assert_eq!(unescape("µ").unwrap(), "µ".as_bytes());
+        assert!(unescape(r"\x3").is_err());
+        assert!(unescape(r"\q").is_err());
+        assert!(unescape("boot\\").is_err());
+    }
+}
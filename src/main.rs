@@ -1,6 +1,3 @@
-// TODO: Implement set port command
-//   TODO: Pause spew while setting port
-// TODO: Support writing to serial port -- maybe use a separate thread for this?
 // Note: RAII log file cleanup has been implemented using LogFile wrapper
 
 use clap::Parser;
@@ -8,13 +5,30 @@ use clap::Parser;
 mod buffer;
 mod commands;
 mod constants;
+mod editor;
 mod error;
+mod filter;
+mod hexdump;
+mod highlight;
+mod limits;
 mod log;
+mod macros;
+mod modem;
+mod replay;
+mod rfc2217;
+mod send;
 mod serial;
 mod settings;
+mod share;
 mod state;
+mod tcp;
+mod telnet;
+mod timestamp;
+mod transport;
+mod tui;
 mod utils;
 mod validation;
+mod xmodem;
 
 fn main() {
     let args = settings::Args::parse();
@@ -22,7 +36,7 @@ fn main() {
     if args.list {
         if let Err(e) = utils::list_ports() {
             eprintln!("Error: {e}");
-            std::process::exit(1);
+            std::process::exit(constants::EXIT_CODE_ERROR);
         }
         return;
     }
@@ -31,35 +45,48 @@ fn main() {
         Ok((config, state)) => (config, state),
         Err(e) => {
             eprintln!("Error: {e}");
-            std::process::exit(1);
+            std::process::exit(constants::EXIT_CODE_ERROR);
         }
     };
     
-    if let Err(e) = run(config, state) {
+    if let Err(e) = run(config, state.clone()) {
         eprintln!("Error: {e}");
-        std::process::exit(1);
+        std::process::exit(constants::EXIT_CODE_ERROR);
+    }
+    if let Some(reason) = utils::stop_reason(&state) {
+        drop(state);
+        std::process::exit(reason.exit_code());
     }
 }
 
 fn run(mut config: settings::Config, state: state::State) -> error::Result<()> {
+    if config.is_headless() {
+        return run_headless(config, state);
+    }
     if !config.disable_welcome.unwrap_or(false) {
         utils::print_welcome();
     }
     config.select_missing()?;
     let settings = settings::get_settings(&config)?;
-    if config.log_on_start.unwrap_or(false) {
+    state::apply_settings(&state, &settings);
+    if config.log_on_start.unwrap_or(false) || settings.log_file.is_some() {
         utils::start_new_log(&settings, &state)?;
     }
 
-    let serial_thread = utils::start_thread(settings.clone(), &state, serial::connect_loop);
-    let command_thread = utils::start_thread(settings.clone(), &state, commands::command_loop);
+    let share_thread = share::start(&settings, &state)?;
+    if settings.tui {
+        tui::enter(settings.scrollback_lines)?;
+    }
+
+    let serial_threads = start_serial_threads(&settings, &state);
+    let command_thread = utils::start_thread(settings.for_port(0), &state, commands::command_loop);
 
-    let serial_result = serial_thread
-        .join()
-        .map_err(|e| error::SpewcapError::ThreadJoin(format!("Serial thread panicked: {:?}", e)))?;
+    let serial_result = join_serial_threads(serial_threads)?;
     let command_result = command_thread
         .join()
         .map_err(|e| error::SpewcapError::ThreadJoin(format!("Command thread panicked: {:?}", e)))?;
+    join_share_thread(share_thread)?;
+    tui::leave()?;
     
     utils::cleanup_logs(&state);
     
@@ -70,6 +97,54 @@ fn run(mut config: settings::Config, state: state::State) -> error::Result<()> {
         eprintln!("Command thread error: {e}");
     }
     
-    // utils::leave_alternate_screen()?;
     Ok(())
 }
+
+fn run_headless(config: settings::Config, state: state::State) -> error::Result<()> {
+    utils::send_messages_to_stderr();
+    config.require_complete()?;
+    let settings = settings::get_settings(&config)?;
+    state::apply_settings(&state, &settings);
+    if config.log_on_start.unwrap_or(false) || settings.log_file.is_some() {
+        utils::start_new_log(&settings, &state)?;
+    }
+
+    let share_thread = share::start(&settings, &state)?;
+    let serial_threads = start_serial_threads(&settings, &state);
+    let serial_result = join_serial_threads(serial_threads)?;
+    join_share_thread(share_thread)?;
+
+    utils::cleanup_logs(&state);
+    serial_result
+}
+
+fn join_share_thread(share_thread: Option<std::thread::JoinHandle<error::Result<()>>>) -> error::Result<()> {
+    if let Some(share_thread) = share_thread {
+        share_thread
+            .join()
+            .map_err(|e| error::SpewcapError::ThreadJoin(format!("Share thread panicked: {:?}", e)))??;
+    }
+    Ok(())
+}
+
+/// One capture thread per port of the session
+fn start_serial_threads(
+    settings: &settings::Settings,
+    state: &state::State,
+) -> Vec<std::thread::JoinHandle<error::Result<()>>> {
+    (0..settings.ports.len().max(1))
+        .map(|index| utils::start_thread(settings.for_port(index), state, serial::connect_loop))
+        .collect()
+}
+
+/// Waits for every capture thread, returning the first error
+fn join_serial_threads(serial_threads: Vec<std::thread::JoinHandle<error::Result<()>>>) -> error::Result<error::Result<()>> {
+    let mut serial_result = Ok(());
+    for serial_thread in serial_threads {
+        let result = serial_thread
+            .join()
+            .map_err(|e| error::SpewcapError::ThreadJoin(format!("Serial thread panicked: {:?}", e)))?;
+        serial_result = serial_result.and(result);
+    }
+    Ok(serial_result)
+}
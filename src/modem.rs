@@ -0,0 +1,292 @@
+use colored::Colorize;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::constants::{BREAK_DURATION, LINE_SEQUENCE_POLL_INTERVAL};
+use crate::error::{Result, SpewcapError};
+use crate::macros::MacroKey;
+use crate::state::State;
+use crate::transport::SourceWriter;
+use crate::utils::{self, print_error, print_message, print_warning};
+
+/// Bits of an RFC 2217 NOTIFY-MODEMSTATE value
+const MODEMSTATE_CTS: u8 = 0x10;
+const MODEMSTATE_DSR: u8 = 0x20;
+const MODEMSTATE_RI: u8 = 0x40;
+const MODEMSTATE_DCD: u8 = 0x80;
+
+/// The input lines of a serial port, `None` for a line the port cannot read (eg. RI on many USB
+/// adapters)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct InputLines {
+    pub cts: Option<bool>,
+    pub dsr: Option<bool>,
+    pub dcd: Option<bool>,
+    pub ri: Option<bool>,
+}
+
+impl InputLines {
+    pub fn from_modem_state(state: u8) -> Self {
+        InputLines {
+            cts: Some(state & MODEMSTATE_CTS != 0),
+            dsr: Some(state & MODEMSTATE_DSR != 0),
+            dcd: Some(state & MODEMSTATE_DCD != 0),
+            ri: Some(state & MODEMSTATE_RI != 0),
+        }
+    }
+
+    /// The lines that differ from `previous`, eg. "CTS on, DCD off"
+    pub fn changes(&self, previous: &InputLines) -> Option<String> {
+        let lines = [
+            ("CTS", self.cts, previous.cts),
+            ("DSR", self.dsr, previous.dsr),
+            ("DCD", self.dcd, previous.dcd),
+            ("RI", self.ri, previous.ri),
+        ];
+        let changes: Vec<String> = lines
+            .iter()
+            .filter_map(|&(name, level, previous)| match (level, previous) {
+                (Some(level), Some(previous)) if level != previous => Some((name, level)),
+                _ => None,
+            })
+            .map(|(name, level)| format!("{name} {}", describe_level(level)))
+            .collect();
+        (!changes.is_empty()).then(|| changes.join(", "))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineStep {
+    Dtr(bool),
+    Rts(bool),
+    Break(bool),
+    Wait(Duration),
+}
+
+/// A `[[line_sequence]]` from the config file, eg. resetting a board into its bootloader
+#[derive(Clone, Debug)]
+pub struct LineSequence {
+    pub name: String,
+    pub key: MacroKey,
+    pub steps: Vec<LineStep>,
+}
+
+/// Parses steps such as "dtr=off rts=on wait=100 rts=off"
+pub fn parse_steps(text: &str) -> std::result::Result<Vec<LineStep>, String> {
+    let steps = text
+        .split_whitespace()
+        .map(|step| {
+            let (line, value) = step
+                .split_once('=')
+                .ok_or_else(|| format!("{step} (expected dtr=, rts=, break= or wait=)"))?;
+            let level = || match value.to_ascii_lowercase().as_str() {
+                "on" | "1" | "true" => Ok(true),
+                "off" | "0" | "false" => Ok(false),
+                _ => Err(format!("{step} (expected on or off)")),
+            };
+            match line.to_ascii_lowercase().as_str() {
+                "dtr" => Ok(LineStep::Dtr(level()?)),
+                "rts" => Ok(LineStep::Rts(level()?)),
+                "break" => Ok(LineStep::Break(level()?)),
+                "wait" => value
+                    .parse::<u64>()
+                    .map(|ms| LineStep::Wait(Duration::from_millis(ms)))
+                    .map_err(|_| format!("{step} (expected a wait in milliseconds)")),
+                _ => Err(format!("{step} (expected dtr=, rts=, break= or wait=)")),
+            }
+        })
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    if steps.is_empty() {
+        return Err("no steps".to_string());
+    }
+    let last_break = steps.iter().rev().find_map(|step| match step {
+        LineStep::Break(on) => Some(*on),
+        _ => None,
+    });
+    if last_break == Some(true) {
+        return Err("break=on is never turned off (end the break with break=off)".to_string());
+    }
+    Ok(steps)
+}
+
+pub fn find_sequence(sequences: &[LineSequence], key: MacroKey) -> Option<&LineSequence> {
+    sequences.iter().find(|sequence| sequence.key == key)
+}
+
+/// Runs the sequence from a thread of its own, so its waits hold up neither keys nor the screen
+pub fn start_sequence(shared_state: &State, sequence: &LineSequence) {
+    if shared_state.sequence_active.swap(true, Ordering::Relaxed) {
+        print_warning("A line sequence is already running, `Esc` cancels it");
+        return;
+    }
+    shared_state.sequence_cancel.store(false, Ordering::Relaxed);
+    let state = State::clone(shared_state);
+    let sequence = sequence.clone();
+    std::thread::spawn(move || {
+        match run_sequence(&state, &sequence) {
+            Ok(true) => print_message(format!("Ran line sequence {} ({})", sequence.name, sequence.key)),
+            Ok(false) => print_warning(&format!("Line sequence {} was cancelled", sequence.name)),
+            Err(e) => print_error(&format!("Line sequence {} failed: {e}", sequence.name)),
+        }
+        state.sequence_active.store(false, Ordering::Relaxed);
+    });
+}
+
+pub fn sequence_running(shared_state: &State) -> bool {
+    shared_state.sequence_active.load(Ordering::Relaxed)
+}
+
+pub fn cancel_sequence(shared_state: &State) {
+    shared_state.sequence_cancel.store(true, Ordering::Relaxed);
+}
+
+/// Runs the steps, returning false if cancelled. A break the sequence left on when it stopped
+/// early is cleared.
+fn run_sequence(shared_state: &State, sequence: &LineSequence) -> Result<bool> {
+    let mut break_on = false;
+    let result = run_steps(shared_state, &sequence.steps, &mut break_on);
+    if break_on {
+        if let Err(e) = with_writer(shared_state, |writer| writer.set_break(false)) {
+            print_error(&format!("Failed to clear BREAK: {e}"));
+        }
+    }
+    result
+}
+
+fn run_steps(shared_state: &State, steps: &[LineStep], break_on: &mut bool) -> Result<bool> {
+    for step in steps {
+        match *step {
+            LineStep::Dtr(level) => set_dtr(shared_state, level)?,
+            LineStep::Rts(level) => set_rts(shared_state, level)?,
+            LineStep::Break(on) => {
+                with_writer(shared_state, |writer| writer.set_break(on))?;
+                *break_on = on;
+            }
+            LineStep::Wait(duration) => {
+                if !sleep(shared_state, duration) {
+                    return Ok(false);
+                }
+            }
+        }
+    }
+    Ok(true)
+}
+
+/// Sleeps in short steps so a cancel is noticed, returning false if cancelled
+fn sleep(shared_state: &State, delay: Duration) -> bool {
+    let end = Instant::now() + delay;
+    loop {
+        if shared_state.sequence_cancel.load(Ordering::Relaxed) || utils::quit_requested(shared_state) {
+            return false;
+        }
+        let now = Instant::now();
+        if now >= end {
+            return true;
+        }
+        std::thread::sleep((end - now).min(LINE_SEQUENCE_POLL_INTERVAL));
+    }
+}
+
+pub fn toggle_dtr(shared_state: &State) -> Result<()> {
+    let level = !shared_state.dtr_level.load(Ordering::Relaxed);
+    set_dtr(shared_state, level)?;
+    print_message(format!("DTR {}", describe_level(level)));
+    Ok(())
+}
+
+pub fn toggle_rts(shared_state: &State) -> Result<()> {
+    let level = !shared_state.rts_level.load(Ordering::Relaxed);
+    set_rts(shared_state, level)?;
+    print_message(format!("RTS {}", describe_level(level)));
+    Ok(())
+}
+
+pub fn send_break(shared_state: &State) -> Result<()> {
+    with_writer(shared_state, |writer| writer.set_break(true))?;
+    std::thread::sleep(BREAK_DURATION);
+    with_writer(shared_state, |writer| writer.set_break(false))?;
+    print_message(format!("Sent BREAK ({} ms)", BREAK_DURATION.as_millis()));
+    Ok(())
+}
+
+fn set_dtr(shared_state: &State, level: bool) -> Result<()> {
+    set_output(shared_state, &shared_state.dtr_level, level, |writer| writer.set_dtr(level))
+}
+
+fn set_rts(shared_state: &State, level: bool) -> Result<()> {
+    set_output(shared_state, &shared_state.rts_level, level, |writer| writer.set_rts(level))
+}
+
+/// Drives an output line, remembering its level so the line can be toggled
+fn set_output<F>(shared_state: &State, current: &AtomicBool, level: bool, set: F) -> Result<()>
+where
+    F: FnOnce(&mut dyn SourceWriter) -> io::Result<()>,
+{
+    with_writer(shared_state, set)?;
+    current.store(level, Ordering::Relaxed);
+    Ok(())
+}
+
+fn with_writer<F>(shared_state: &State, action: F) -> Result<()>
+where
+    F: FnOnce(&mut dyn SourceWriter) -> io::Result<()>,
+{
+    let mut port_writer = shared_state
+        .port_writer
+        .lock()
+        .map_err(|e| SpewcapError::Transmit(format!("Failed to acquire lock on port writer: {e}")))?;
+    let writer = port_writer.as_mut().ok_or(SpewcapError::PortNotConnected)?;
+    action(writer.as_mut()).map_err(|e| SpewcapError::LineControl(e.to_string()))
+}
+
+fn describe_level(level: bool) -> colored::ColoredString {
+    if level {
+        "on".green()
+    } else {
+        "off".yellow()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::override_colors;
+
+    #[test]
+    fn test_parse_steps() {
+        assert_eq!(
+            parse_steps("dtr=off rts=on wait=100 DTR=1 rts=off break=on wait=50 break=off").unwrap(),
+            vec![
+                LineStep::Dtr(false),
+                LineStep::Rts(true),
+                LineStep::Wait(Duration::from_millis(100)),
+                LineStep::Dtr(true),
+                LineStep::Rts(false),
+                LineStep::Break(true),
+                LineStep::Wait(Duration::from_millis(50)),
+                LineStep::Break(false),
+            ]
+        );
+        assert!(parse_steps("dtr=off break=on wait=250").is_err());
+        assert!(parse_steps("break=on break=off break=on").is_err());
+        assert!(parse_steps("").is_err());
+        assert!(parse_steps("dtr=maybe").is_err());
+        assert!(parse_steps("cts=on").is_err());
+        assert!(parse_steps("wait=soon").is_err());
+    }
+
+    #[test]
+    fn test_input_line_changes() {
+        let previous = InputLines::from_modem_state(MODEMSTATE_CTS | MODEMSTATE_DCD);
+        let current = InputLines::from_modem_state(MODEMSTATE_CTS | MODEMSTATE_DSR);
+        let _colors = override_colors(false);
+        let changes = current.changes(&previous).unwrap();
+        assert_eq!(changes, "DSR on, DCD off");
+        assert_eq!(current.changes(&current), None);
+        // a line that could not be read before is not reported as changed
+        let ringing = InputLines { ri: Some(true), ..current };
+        let unreadable_ri = InputLines { ri: None, ..previous };
+        assert_eq!(ringing.changes(&unreadable_ri).unwrap(), "DSR on, DCD off");
+    }
+}
@@ -0,0 +1,278 @@
+use chrono::TimeDelta;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use crate::constants::*;
+use crate::error::Result;
+use crate::settings::Settings;
+use crate::state::State;
+use crate::timestamp::{parse_timestamp, TimestampMode};
+use crate::transport::{ReadResult, Source, SourceWriter};
+use crate::utils::print_message;
+
+/// When the data of a recording becomes due during playback
+enum Timing {
+    /// A timestamped text log: each line's end offset in the data and the time it is due
+    Lines(Vec<(Duration, usize)>),
+    /// No timing information (a raw capture or a log without timestamps), so the bytes are
+    /// paced at this many per second, as the port would have delivered them
+    ByteRate(f64),
+}
+
+/// A capture file loaded for playback, with log timestamps stripped from the data
+struct Recording {
+    data: Vec<u8>,
+    timing: Timing,
+}
+
+impl Recording {
+    fn load(path: &Path, settings: &Settings) -> Result<Self> {
+        Ok(Self::parse(fs::read(path)?, settings))
+    }
+
+    /// Logs are recognised by a timestamp on their first line, read with the timestamp settings
+    fn parse(contents: Vec<u8>, settings: &Settings) -> Self {
+        let mode = settings.timestamp_mode;
+        let format = settings.timestamp_format.as_deref();
+        let timestamp = |line: &[u8]| -> Option<(TimeDelta, usize)> {
+            let stamped = line.strip_prefix(b"[")?;
+            let end = stamped.windows(2).position(|window| window == b"] ")?;
+            let text = std::str::from_utf8(&stamped[..end]).ok()?;
+            // the prefix is the brackets, the timestamp and a space
+            Some((parse_timestamp(mode, format, text)?, end + 3))
+        };
+        let timed = contents
+            .split_inclusive(|&byte| byte == b'\n')
+            .next()
+            .is_some_and(|line| timestamp(line).is_some());
+        if !timed {
+            let bytes_per_second = f64::from(settings.baud_rate) / SERIAL_BITS_PER_BYTE;
+            return Recording {
+                data: contents,
+                timing: Timing::ByteRate(bytes_per_second),
+            };
+        }
+        let mut data = Vec::with_capacity(contents.len());
+        let mut due = Vec::new();
+        let mut clock = TimeDelta::zero();
+        let mut previous = None;
+        for line in contents.split_inclusive(|&byte| byte == b'\n') {
+            match timestamp(line) {
+                Some((time, prefix_len)) => {
+                    let step = match (mode, previous) {
+                        (TimestampMode::Delta, _) => time,
+                        (_, Some(previous)) => time - previous,
+                        (_, None) => TimeDelta::zero(),
+                    };
+                    // a wall-clock time wrapping at midnight must not go back in time
+                    clock += step.max(TimeDelta::zero());
+                    previous = Some(time);
+                    data.extend_from_slice(&line[prefix_len..]);
+                }
+                // the rest of a line that was logged in parts has no timestamp of its own
+                None => data.extend_from_slice(line),
+            }
+            due.push((clock.to_std().unwrap_or_default(), data.len()));
+        }
+        Recording {
+            data,
+            timing: Timing::Lines(due),
+        }
+    }
+
+    fn duration(&self) -> Duration {
+        match &self.timing {
+            Timing::Lines(due) => due.last().map_or(Duration::ZERO, |&(time, _)| time),
+            Timing::ByteRate(rate) => Duration::from_secs_f64(self.data.len() as f64 / rate),
+        }
+    }
+
+    /// How much of the data has been played by `clock`
+    fn offset_at(&self, clock: Duration) -> usize {
+        match &self.timing {
+            Timing::Lines(due) => match due.partition_point(|&(time, _)| time <= clock) {
+                0 => 0,
+                lines => due[lines - 1].1,
+            },
+            Timing::ByteRate(rate) => ((clock.as_secs_f64() * rate).round() as usize).min(self.data.len()),
+        }
+    }
+}
+
+/// Plays a saved log or raw capture through the capture pipeline as if it were a live port
+pub struct ReplaySource {
+    settings: Settings,
+    shared_state: State,
+    recording: Option<Recording>,
+    clock: Duration,
+    position: usize,
+    last_tick: Instant,
+    finished: bool,
+}
+
+impl ReplaySource {
+    pub fn new(settings: &Settings, shared_state: &State) -> Self {
+        ReplaySource {
+            settings: settings.clone(),
+            shared_state: shared_state.clone(),
+            recording: None,
+            clock: Duration::ZERO,
+            position: 0,
+            last_tick: Instant::now(),
+            finished: false,
+        }
+    }
+
+    fn apply_seek(&mut self) {
+        let seek_ms = self.shared_state.replay_seek_ms.swap(0, Ordering::Relaxed);
+        let Some(recording) = &self.recording else {
+            return;
+        };
+        if seek_ms == 0 {
+            return;
+        }
+        let step = Duration::from_millis(seek_ms.unsigned_abs());
+        self.clock = if seek_ms < 0 {
+            self.clock.saturating_sub(step)
+        } else {
+            (self.clock + step).min(recording.duration())
+        };
+        self.position = recording.offset_at(self.clock);
+        self.finished = false;
+        print_message(format!(
+            "Replay at {} of {}",
+            format_position(self.clock),
+            format_position(recording.duration())
+        ));
+    }
+
+    fn advance_clock(&mut self) {
+        let now = Instant::now();
+        // reading stops while a prompt is open, which should not count as playback time
+        let elapsed = (now - self.last_tick).min(REPLAY_MAX_STEP);
+        self.last_tick = now;
+        if self.shared_state.replay_paused.load(Ordering::Relaxed) {
+            return;
+        }
+        // stopping at the end keeps a seek back relative to the end of the recording
+        let end = self.recording.as_ref().map_or(Duration::ZERO, Recording::duration);
+        self.clock = (self.clock + elapsed.mul_f64(self.settings.replay_speed)).min(end);
+    }
+}
+
+impl Source for ReplaySource {
+    fn name(&self) -> String {
+        self.settings.port.clone()
+    }
+
+    fn summary(&self) -> String {
+        let speed = self.settings.replay_speed;
+        if speed == 0.0 {
+            return "replay as fast as possible".to_string();
+        }
+        match self.recording.as_ref().map(|recording| &recording.timing) {
+            Some(Timing::Lines(_)) => format!("replay at {speed}x, timed by the log's timestamps"),
+            Some(Timing::ByteRate(_)) => format!("replay at {speed}x, paced at {} baud", self.settings.baud_rate),
+            None => format!("replay at {speed}x"),
+        }
+    }
+
+    fn open(&mut self) -> Result<()> {
+        let path = self.settings.replay.as_deref().unwrap_or(Path::new(&self.settings.port));
+        self.recording = Some(Recording::load(path, &self.settings)?);
+        self.clock = Duration::ZERO;
+        self.position = 0;
+        self.last_tick = Instant::now();
+        self.finished = false;
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        self.recording = None;
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> ReadResult {
+        self.apply_seek();
+        self.advance_clock();
+        let Some(recording) = &self.recording else {
+            return ReadResult::Error;
+        };
+        if self.position >= recording.data.len() {
+            if self.settings.headless {
+                return ReadResult::End;
+            }
+            // interactive sessions stay open so the recording can be sought back into
+            if !self.finished {
+                self.finished = true;
+                print_message("Replay finished, `[` seeks back");
+            }
+            return ReadResult::NoData;
+        }
+        let due = match self.settings.replay_speed {
+            0.0 => recording.data.len(),
+            _ => recording.offset_at(self.clock),
+        };
+        if due <= self.position {
+            return ReadResult::NoData;
+        }
+        let size = (due - self.position).min(buffer.len());
+        buffer[..size].copy_from_slice(&recording.data[self.position..self.position + size]);
+        self.position += size;
+        ReadResult::Data(size)
+    }
+
+    fn writer(&self) -> Option<Box<dyn SourceWriter>> {
+        None
+    }
+}
+
+fn format_position(position: Duration) -> String {
+    let seconds = position.as_secs();
+    format!("{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::init_state;
+
+    #[test]
+    fn test_parse_timestamped_log() {
+        let mut settings = Settings::for_tests("boot.log");
+        settings.timestamp_mode = TimestampMode::Elapsed;
+        let log = b"[00:00:01:000ms] U-Boot\n[00:00:03:500ms] Starting kernel\n...\n";
+        let recording = Recording::parse(log.to_vec(), &settings);
+        assert_eq!(recording.data, b"U-Boot\nStarting kernel\n...\n");
+        assert_eq!(recording.duration(), Duration::from_millis(2500));
+        assert_eq!(recording.offset_at(Duration::ZERO), 7);
+        assert_eq!(recording.offset_at(Duration::from_millis(2499)), 7);
+        assert_eq!(recording.offset_at(Duration::from_millis(2500)), 27);
+    }
+
+    #[test]
+    fn test_parse_raw_capture_paces_at_baud_rate() {
+        let mut settings = Settings::for_tests("capture.bin");
+        settings.baud_rate = 9600;
+        let recording = Recording::parse(vec![0xaa; 1920], &settings);
+        assert_eq!(recording.duration(), Duration::from_secs(2));
+        assert_eq!(recording.offset_at(Duration::from_secs(1)), 960);
+    }
+
+    #[test]
+    fn test_headless_replay_ends_after_the_recording() {
+        let path = std::env::temp_dir().join(format!("spewcap_replay_test_{}.log", std::process::id()));
+        fs::write(&path, b"line 1\nline 2\n").unwrap();
+        let mut settings = Settings::for_tests(&path.to_string_lossy());
+        settings.replay = Some(path.clone());
+        settings.replay_speed = 0.0;
+        settings.headless = true;
+        let mut source = ReplaySource::new(&settings, &init_state());
+        source.open().unwrap();
+        let mut buffer = [0; 64];
+        assert!(matches!(source.read(&mut buffer), ReadResult::Data(14)));
+        assert!(matches!(source.read(&mut buffer), ReadResult::End));
+        fs::remove_file(path).unwrap();
+    }
+}
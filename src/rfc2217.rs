@@ -0,0 +1,182 @@
+use serialport5::{DataBits, FlowControl, Parity, StopBits};
+
+use crate::settings::Settings;
+use crate::telnet::{IAC, SB, SE};
+
+/// Telnet option number of COM-PORT-OPTION
+pub const COM_PORT_OPTION: u8 = 44;
+
+const SET_BAUDRATE: u8 = 1;
+const SET_DATASIZE: u8 = 2;
+const SET_PARITY: u8 = 3;
+const SET_STOPSIZE: u8 = 4;
+const SET_CONTROL: u8 = 5;
+const NOTIFY_MODEMSTATE: u8 = 7;
+/// Servers answer a command with its code plus this offset
+const SERVER_OFFSET: u8 = 100;
+
+const CONTROL_NO_FLOW_CONTROL: u8 = 1;
+const CONTROL_XON_XOFF: u8 = 2;
+const CONTROL_HARDWARE: u8 = 3;
+const CONTROL_BREAK_ON: u8 = 5;
+const CONTROL_BREAK_OFF: u8 = 6;
+const CONTROL_DTR_ON: u8 = 8;
+const CONTROL_DTR_OFF: u8 = 9;
+const CONTROL_RTS_ON: u8 = 11;
+const CONTROL_RTS_OFF: u8 = 12;
+
+/// A server's confirmation of a setting, carrying the value it actually applied
+#[derive(Debug, PartialEq, Eq)]
+pub enum ServerReply {
+    BaudRate(u32),
+    DataSize(u8),
+    Parity(u8),
+    StopSize(u8),
+    /// The state of the port's input lines, sent when one of them changes
+    ModemState(u8),
+}
+
+fn command(code: u8, value: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![IAC, SB, COM_PORT_OPTION, code];
+    for &byte in value {
+        bytes.push(byte);
+        // IAC inside a subnegotiation has to be doubled
+        if byte == IAC {
+            bytes.push(IAC);
+        }
+    }
+    bytes.extend_from_slice(&[IAC, SE]);
+    bytes
+}
+
+pub fn set_baud_rate(baud_rate: u32) -> Vec<u8> {
+    command(SET_BAUDRATE, &baud_rate.to_be_bytes())
+}
+
+pub fn set_dtr(level: bool) -> Vec<u8> {
+    command(SET_CONTROL, &[if level { CONTROL_DTR_ON } else { CONTROL_DTR_OFF }])
+}
+
+pub fn set_rts(level: bool) -> Vec<u8> {
+    command(SET_CONTROL, &[if level { CONTROL_RTS_ON } else { CONTROL_RTS_OFF }])
+}
+
+pub fn set_break(on: bool) -> Vec<u8> {
+    command(SET_CONTROL, &[if on { CONTROL_BREAK_ON } else { CONTROL_BREAK_OFF }])
+}
+
+/// Commands applying the line settings, the way a local port is configured when opened
+pub fn configure(settings: &Settings) -> Vec<u8> {
+    let control = match settings.flow_control {
+        FlowControl::None => CONTROL_NO_FLOW_CONTROL,
+        FlowControl::Software => CONTROL_XON_XOFF,
+        FlowControl::Hardware => CONTROL_HARDWARE,
+    };
+    let mut commands = set_baud_rate(settings.baud_rate);
+    commands.extend(command(SET_DATASIZE, &[data_size(settings)]));
+    commands.extend(command(SET_PARITY, &[parity(settings)]));
+    commands.extend(command(SET_STOPSIZE, &[stop_size(settings)]));
+    commands.extend(command(SET_CONTROL, &[control]));
+    if let Some(dtr) = settings.dtr {
+        commands.extend(set_dtr(dtr));
+    }
+    if let Some(rts) = settings.rts {
+        commands.extend(set_rts(rts));
+    }
+    commands
+}
+
+fn data_size(settings: &Settings) -> u8 {
+    match settings.data_bits {
+        DataBits::Five => 5,
+        DataBits::Six => 6,
+        DataBits::Seven => 7,
+        DataBits::Eight => 8,
+    }
+}
+
+fn parity(settings: &Settings) -> u8 {
+    match settings.parity {
+        Parity::None => 1,
+        Parity::Odd => 2,
+        Parity::Even => 3,
+    }
+}
+
+fn stop_size(settings: &Settings) -> u8 {
+    match settings.stop_bits {
+        StopBits::One => 1,
+        StopBits::Two => 2,
+    }
+}
+
+/// Describes a setting the server applied differently from what was requested
+pub fn reply_mismatch(settings: &Settings, reply: &ServerReply) -> Option<String> {
+    let (name, requested, applied) = match *reply {
+        ServerReply::BaudRate(baud_rate) => ("baud rate", settings.baud_rate, baud_rate),
+        ServerReply::DataSize(size) => ("data bits", data_size(settings).into(), size.into()),
+        ServerReply::Parity(value) => ("parity", parity(settings).into(), value.into()),
+        ServerReply::StopSize(size) => ("stop bits", stop_size(settings).into(), size.into()),
+        ServerReply::ModemState(_) => return None,
+    };
+    (requested != applied).then(|| format!("server set {name} to {applied} instead of {requested}"))
+}
+
+/// Parses the payload of a COM-PORT-OPTION subnegotiation sent by the server
+pub fn parse_reply(payload: &[u8]) -> Option<ServerReply> {
+    let (&code, value) = payload.split_first()?;
+    let reply = match code.checked_sub(SERVER_OFFSET)? {
+        SET_BAUDRATE => ServerReply::BaudRate(u32::from_be_bytes(value.try_into().ok()?)),
+        SET_DATASIZE => ServerReply::DataSize(*value.first()?),
+        SET_PARITY => ServerReply::Parity(*value.first()?),
+        SET_STOPSIZE => ServerReply::StopSize(*value.first()?),
+        NOTIFY_MODEMSTATE => ServerReply::ModemState(*value.first()?),
+        _ => return None,
+    };
+    Some(reply)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_baud_rate_escapes_iac() {
+        assert_eq!(
+            set_baud_rate(115200),
+            vec![IAC, SB, COM_PORT_OPTION, 1, 0x00, 0x01, 0xc2, 0x00, IAC, SE]
+        );
+        assert_eq!(
+            set_baud_rate(0xff),
+            vec![IAC, SB, COM_PORT_OPTION, 1, 0, 0, 0, IAC, IAC, IAC, SE]
+        );
+    }
+
+    #[test]
+    fn test_configure_line_settings() {
+        let mut settings = Settings::for_tests("rfc2217://lab:2001");
+        settings.parity = Parity::Even;
+        settings.flow_control = FlowControl::Hardware;
+        settings.dtr = Some(false);
+        let commands = configure(&settings);
+        assert!(commands.windows(6).any(|w| w == [IAC, SB, COM_PORT_OPTION, SET_PARITY, 3, IAC]));
+        assert!(commands.windows(6).any(|w| w == [IAC, SB, COM_PORT_OPTION, SET_CONTROL, 3, IAC]));
+        assert!(commands.ends_with(&set_dtr(false)));
+    }
+
+    #[test]
+    fn test_parse_reply() {
+        let settings = Settings::for_tests("rfc2217://lab:2001");
+        let reply = parse_reply(&[101, 0, 0, 0x25, 0x80]).unwrap();
+        assert_eq!(reply, ServerReply::BaudRate(9600));
+        assert_eq!(
+            reply_mismatch(&settings, &reply),
+            Some("server set baud rate to 9600 instead of 115200".to_string())
+        );
+        assert_eq!(reply_mismatch(&settings, &parse_reply(&[103, 1]).unwrap()), None);
+        assert_eq!(parse_reply(&[1, 0, 0, 0x25, 0x80]), None);
+        let modem_state = parse_reply(&[107, 0x30]).unwrap();
+        assert_eq!(modem_state, ServerReply::ModemState(0x30));
+        assert_eq!(reply_mismatch(&settings, &modem_state), None);
+    }
+}
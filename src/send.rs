@@ -0,0 +1,347 @@
+use dialoguer::{Input, Select};
+use regex::Regex;
+use rfd::FileDialog;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use crate::constants::*;
+use crate::error::{Result, SpewcapError};
+use crate::serial;
+use crate::state::State;
+use crate::utils::{self, print_error, print_message, print_success, print_warning};
+use crate::validation;
+use crate::xmodem::{self, Protocol, TransferRequest};
+
+/// How a file is paced while it is sent
+#[derive(Clone, Debug)]
+pub enum Pacing {
+    /// As fast as the port takes it
+    Raw,
+    /// A pause after every byte
+    CharDelay(Duration),
+    /// A pause after every line
+    LineDelay(Duration),
+    /// Every line waits for the device to echo it back
+    Echo,
+    /// Every line waits for output matching a prompt
+    Prompt(Regex),
+}
+
+/// What the send prompt picked: a paced send, or a file transfer protocol
+enum Method {
+    Paced(Pacing),
+    Transfer(Protocol),
+}
+
+impl Pacing {
+    fn describe(&self) -> String {
+        match self {
+            Pacing::Raw => "raw".to_string(),
+            Pacing::CharDelay(delay) => format!("{} ms per character", delay.as_millis()),
+            Pacing::LineDelay(delay) => format!("{} ms per line", delay.as_millis()),
+            Pacing::Echo => "waiting for each line's echo".to_string(),
+            Pacing::Prompt(prompt) => format!("waiting for `{prompt}` after each line"),
+        }
+    }
+}
+
+/// Asks for a file and how to send it, then sends it from a thread of its own, or hands it to
+/// the capture thread for an XMODEM/YMODEM transfer
+pub fn start(shared_state: &State) -> Result<()> {
+    if shared_state.transfer_active.load(Ordering::Relaxed) {
+        print_warning("A file is already being sent, `Esc` cancels it");
+        return Ok(());
+    }
+    if !shared_state.connected.load(Ordering::Relaxed) {
+        return Err(SpewcapError::PortNotConnected);
+    }
+    let (path, method) = utils::run_prompt(shared_state, select_send)?;
+    let data = fs::read(&path)?;
+    shared_state.transfer_cancel.store(false, Ordering::Relaxed);
+    let pacing = match method {
+        Method::Paced(pacing) => pacing,
+        Method::Transfer(protocol) => {
+            xmodem::request(shared_state, TransferRequest { path, data, protocol })?;
+            shared_state.transfer_active.store(true, Ordering::Relaxed);
+            return Ok(());
+        }
+    };
+    shared_state.transfer_active.store(true, Ordering::Relaxed);
+    let state = State::clone(shared_state);
+    std::thread::spawn(move || {
+        let name = file_name(&path);
+        print_message(format!("Sending {name} ({} bytes, {}), `Esc` cancels", data.len(), pacing.describe()));
+        match send(&state, &data, &pacing) {
+            Ok(true) => print_success(&format!("Sent {name}")),
+            Ok(false) => print_warning(&format!("Sending {name} was cancelled")),
+            Err(e) => print_error(&format!("Failed to send {name}: {e}")),
+        }
+        stop_tap(&state);
+        state.transfer_active.store(false, Ordering::Relaxed);
+    });
+    Ok(())
+}
+
+pub fn is_active(shared_state: &State) -> bool {
+    shared_state.transfer_active.load(Ordering::Relaxed)
+}
+
+pub fn cancel(shared_state: &State) {
+    shared_state.transfer_cancel.store(true, Ordering::Relaxed);
+    if xmodem::cancel_pending(shared_state) {
+        shared_state.transfer_active.store(false, Ordering::Relaxed);
+        print_warning("Transfer cancelled before the receiver started");
+    }
+}
+
+/// Keeps what the main port receives while a transfer waits for a reply
+pub fn tap_received(shared_state: &State, data: &[u8]) {
+    let Ok(mut tap) = shared_state.received_tap.lock() else {
+        return;
+    };
+    if let Some(received) = tap.as_mut() {
+        received.extend_from_slice(data);
+        // only the latest output can hold the echo or prompt being waited for
+        if received.len() > SEND_TAP_LIMIT {
+            let excess = received.len() - SEND_TAP_LIMIT;
+            received.drain(..excess);
+        }
+    }
+}
+
+fn select_send() -> Result<(PathBuf, Method)> {
+    let path: String = Input::new()
+        .with_prompt("File to send (empty to browse)")
+        .allow_empty(true)
+        .validate_with(|input: &String| -> std::result::Result<(), String> {
+            if input.is_empty() {
+                return Ok(());
+            }
+            validation::validate_send_file(input).map(|_| ()).map_err(|e| e.to_string())
+        })
+        .interact_text()
+        .map_err(|e| SpewcapError::Dialog(format!("No file entered: {e}")))?;
+    let path = match path.as_str() {
+        "" => FileDialog::new()
+            .set_title("Send File")
+            .pick_file()
+            .ok_or_else(|| SpewcapError::Dialog("No file selected".to_string()))?,
+        path => validation::validate_send_file(path)?,
+    };
+    let method = Select::new()
+        .with_prompt("Send")
+        .default(0)
+        .items(&[
+            "Raw (as fast as the port takes it)",
+            "With a delay after each character",
+            "With a delay after each line",
+            "Line by line, waiting for each line's echo",
+            "Line by line, waiting for a prompt",
+            "XMODEM-CRC (128-byte blocks)",
+            "XMODEM-1K (1024-byte blocks)",
+            "YMODEM (with the file name and size)",
+        ])
+        .interact()
+        .map_err(|e| SpewcapError::Dialog(format!("No send method selected: {e}")))?;
+    let pacing = match method {
+        0 => Pacing::Raw,
+        1 => Pacing::CharDelay(select_delay("Delay per character (ms)", SEND_DEFAULT_CHAR_DELAY_MS)?),
+        2 => Pacing::LineDelay(select_delay("Delay per line (ms)", SEND_DEFAULT_LINE_DELAY_MS)?),
+        3 => Pacing::Echo,
+        5 => return Ok((path, Method::Transfer(Protocol::XmodemCrc))),
+        6 => return Ok((path, Method::Transfer(Protocol::Xmodem1k))),
+        7 => return Ok((path, Method::Transfer(Protocol::Ymodem))),
+        _ => {
+            let prompt: String = Input::new()
+                .with_prompt("Prompt regex")
+                .validate_with(|input: &String| -> std::result::Result<(), String> {
+                    validation::validate_regex(input).map(|_| ()).map_err(|e| e.to_string())
+                })
+                .interact_text()
+                .map_err(|e| SpewcapError::Dialog(format!("No prompt entered: {e}")))?;
+            Pacing::Prompt(validation::validate_regex(&prompt)?)
+        }
+    };
+    Ok((path, Method::Paced(pacing)))
+}
+
+fn select_delay(prompt: &str, default: u64) -> Result<Duration> {
+    let delay: u64 = Input::new()
+        .with_prompt(prompt)
+        .default(default)
+        .interact_text()
+        .map_err(|e| SpewcapError::Dialog(format!("No delay entered: {e}")))?;
+    Ok(Duration::from_millis(delay))
+}
+
+/// Sends the data, returning false if it was cancelled
+fn send(shared_state: &State, data: &[u8], pacing: &Pacing) -> Result<bool> {
+    let chunks = split(data, pacing);
+    // line endings are rewritten when sending by line, so count what is actually sent
+    let mut progress = Progress::new(chunks.iter().map(Vec::len).sum());
+    for chunk in &chunks {
+        if cancelled(shared_state) {
+            return Ok(false);
+        }
+        if matches!(pacing, Pacing::Echo | Pacing::Prompt(_)) {
+            start_tap(shared_state);
+        }
+        serial::write_to_port(shared_state, chunk)?;
+        let replied = match pacing {
+            Pacing::Raw => true,
+            Pacing::CharDelay(delay) | Pacing::LineDelay(delay) => sleep(shared_state, *delay),
+            Pacing::Echo => {
+                let line = chunk.strip_suffix(TRANSMIT_LINE_ENDING.as_bytes()).unwrap_or(chunk);
+                let line = String::from_utf8_lossy(line);
+                let line = line.trim_end();
+                // an empty line echoes nothing worth waiting for
+                line.is_empty() || wait_for(shared_state, "echo", |received| received.contains(line))?
+            }
+            Pacing::Prompt(prompt) => wait_for(shared_state, "prompt", |received| prompt.is_match(received))?,
+        };
+        if !replied {
+            return Ok(false);
+        }
+        progress.advance(chunk.len());
+    }
+    Ok(true)
+}
+
+/// The pieces written to the port one at a time, lines ending with the transmit line ending
+fn split(data: &[u8], pacing: &Pacing) -> Vec<Vec<u8>> {
+    match pacing {
+        Pacing::Raw => data.chunks(SEND_CHUNK_SIZE).map(<[u8]>::to_vec).collect(),
+        Pacing::CharDelay(_) => data.chunks(1).map(<[u8]>::to_vec).collect(),
+        _ => data
+            .split_inclusive(|&byte| byte == b'\n')
+            .map(|line| {
+                let line = line.strip_suffix(b"\n").unwrap_or(line);
+                let mut line = line.strip_suffix(b"\r").unwrap_or(line).to_vec();
+                line.extend_from_slice(TRANSMIT_LINE_ENDING.as_bytes());
+                line
+            })
+            .collect(),
+    }
+}
+
+fn start_tap(shared_state: &State) {
+    if let Ok(mut tap) = shared_state.received_tap.lock() {
+        *tap = Some(Vec::new());
+    }
+}
+
+fn stop_tap(shared_state: &State) {
+    if let Ok(mut tap) = shared_state.received_tap.lock() {
+        *tap = None;
+    }
+}
+
+/// Polls the received output until `done` accepts it, returning false if cancelled
+fn wait_for<F: Fn(&str) -> bool>(shared_state: &State, what: &str, done: F) -> Result<bool> {
+    let start = Instant::now();
+    loop {
+        if cancelled(shared_state) {
+            return Ok(false);
+        }
+        let matched = shared_state
+            .received_tap
+            .lock()
+            .map(|tap| tap.as_ref().is_some_and(|received| done(&String::from_utf8_lossy(received))))
+            .unwrap_or(false);
+        if matched {
+            return Ok(true);
+        }
+        if start.elapsed() >= SEND_WAIT_TIMEOUT {
+            return Err(SpewcapError::Transmit(format!(
+                "no {what} within {} seconds",
+                SEND_WAIT_TIMEOUT.as_secs()
+            )));
+        }
+        std::thread::sleep(SEND_POLL_INTERVAL);
+    }
+}
+
+/// Sleeps in short steps so a cancel is noticed, returning false if cancelled
+fn sleep(shared_state: &State, delay: Duration) -> bool {
+    let end = Instant::now() + delay;
+    loop {
+        if cancelled(shared_state) {
+            return false;
+        }
+        let now = Instant::now();
+        if now >= end {
+            return true;
+        }
+        std::thread::sleep((end - now).min(SEND_POLL_INTERVAL));
+    }
+}
+
+fn cancelled(shared_state: &State) -> bool {
+    shared_state.transfer_cancel.load(Ordering::Relaxed) || utils::quit_requested(shared_state)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map_or_else(|| path.display().to_string(), |name| name.to_string_lossy().into_owned())
+}
+
+/// Reports how far a transfer has got, at most once per `SEND_PROGRESS_INTERVAL`
+struct Progress {
+    total: usize,
+    sent: usize,
+    last_report: Instant,
+}
+
+impl Progress {
+    fn new(total: usize) -> Self {
+        Progress {
+            total,
+            sent: 0,
+            last_report: Instant::now(),
+        }
+    }
+
+    fn advance(&mut self, bytes: usize) {
+        self.sent += bytes;
+        if self.last_report.elapsed() >= SEND_PROGRESS_INTERVAL && self.sent < self.total {
+            self.last_report = Instant::now();
+            print_message(format!(
+                "Sent {}% ({} of {} bytes)",
+                self.sent * 100 / self.total.max(1),
+                self.sent,
+                self.total
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::init_state;
+
+    #[test]
+    fn test_split_lines_uses_transmit_line_ending() {
+        let chunks = split(b"echo 1\r\necho 2\n\nlast", &Pacing::Echo);
+        let expected: Vec<Vec<u8>> = ["echo 1", "echo 2", "", "last"]
+            .iter()
+            .map(|line| format!("{line}{TRANSMIT_LINE_ENDING}").into_bytes())
+            .collect();
+        assert_eq!(chunks, expected);
+        assert_eq!(split(b"abc", &Pacing::CharDelay(Duration::ZERO)).len(), 3);
+    }
+
+    #[test]
+    fn test_wait_for_prompt_in_tapped_output() {
+        let state = init_state();
+        start_tap(&state);
+        tap_received(&state, b"ls\r\nboot.sh\r\n");
+        tap_received(&state, b"root@board:~# ");
+        let prompt = Regex::new(r"# $").unwrap();
+        assert!(wait_for(&state, "prompt", |received| prompt.is_match(received)).unwrap());
+        cancel(&state);
+        assert!(!wait_for(&state, "prompt", |_| false).unwrap());
+    }
+}
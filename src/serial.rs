@@ -1,47 +1,106 @@
+use bytes::BytesMut;
 use colored::Colorize;
 use serialport5::{self, SerialPort, SerialPortBuilder};
+use std::borrow::Cow;
 use std::io::{self, BufWriter, Read, Write};
 use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
 
-use crate::buffer::LineBuffer;
+use crate::buffer::{Decoding, Line, LineBuffer, NewlineMode};
 use crate::constants::*;
+use crate::hexdump::HexDumper;
+use crate::highlight::{highlight_line, HighlightRule};
+use crate::limits::CaptureLimits;
+use crate::log::{LogFile, LogFormat};
+use crate::modem::InputLines;
 use crate::settings::Settings;
+use crate::send;
+use crate::share::{self, ShareMode};
 use crate::state::State;
-use crate::utils::{get_log_state, print_error, print_message, quit_requested, sleep_ms};
-use crate::error::Result;
+use crate::timestamp::{Moment, Timestamper};
+use crate::transport::{self, ReadResult, Source, SourceWriter};
+use crate::tui::ScreenWriter;
+use crate::utils::{
+    get_log_state, print_error, print_message, quit_requested, request_quit_with_state, sleep_ms, stop_capture,
+};
+use crate::error::{Result, SpewcapError};
 use crate::validation;
+use crate::xmodem;
 
 pub enum ConnectionStatus {
     Connected,
     NotConnected,
     Disconnected,
+    Closed,
+    Finished,
 }
 
-pub fn connect_loop(settings: Settings, shared_state: State) -> Result<()> {
+pub fn connect_loop(mut settings: Settings, shared_state: State) -> Result<()> {
+    // with several ports, port changes and transmitting only apply to the main one
+    let main_port = settings.port_index == 0;
     let mut first_attempt = true;
-    let port_name = &settings.port;
+    let mut limits = CaptureLimits::new(&settings);
+    let mut source = transport::source_for(&settings, &shared_state);
     loop {
         if quit_requested(&shared_state) {
             break;
         }
-        match open_serial_port(port_name, settings.baud_rate) {
-            Some(port) => {
-                print_status(port_name, ConnectionStatus::Connected);
-                let mut stdout = Box::new(BufWriter::with_capacity(STDOUT_BUFFER_CAPACITY, io::stdout()));
-                let status = read_loop(port, &shared_state, &mut stdout);
+        if let Some(reason) = limits.check_timeout() {
+            stop_capture(&shared_state, reason);
+            break;
+        }
+        if let Some(new_settings) = main_port.then(|| take_pending_settings(&shared_state)).flatten() {
+            let new_source = transport::source_for(&new_settings, &shared_state);
+            announce_source_change(source.as_ref(), new_source.as_ref(), &shared_state);
+            settings = new_settings;
+            source = new_source;
+            first_attempt = true;
+        }
+        match source.open() {
+            Ok(()) => {
+                print_status(source.as_ref(), ConnectionStatus::Connected);
+                if main_port {
+                    set_port_writer(&shared_state, source.writer());
+                    shared_state.connected.store(true, Ordering::Relaxed);
+                    // opening a port asserts DTR and RTS unless configured otherwise
+                    shared_state.dtr_level.store(settings.dtr.unwrap_or(true), Ordering::Relaxed);
+                    shared_state.rts_level.store(settings.rts.unwrap_or(true), Ordering::Relaxed);
+                }
+                let stdout: Box<dyn Write> = if settings.quiet {
+                    Box::new(io::sink())
+                } else if settings.tui {
+                    Box::new(ScreenWriter)
+                } else {
+                    Box::new(BufWriter::with_capacity(STDOUT_BUFFER_CAPACITY, io::stdout()))
+                };
+                // several ports share one timestamp base so their lines can be lined up
+                let timestamp_start = match settings.label {
+                    Some(_) => shared_state.capture_start,
+                    None => Moment::now(),
+                };
+                let mut output = CaptureOutput::new(stdout, &settings, timestamp_start);
+                let status = read_loop(source.as_mut(), &shared_state, &mut limits, &mut output);
+                if main_port {
+                    set_port_writer(&shared_state, None);
+                    shared_state.connected.store(false, Ordering::Relaxed);
+                }
+                source.close();
                 match status {
                     ConnectionStatus::Connected => break, // still connected means we are quitting
-                    ConnectionStatus::Disconnected => {
-                        print_status(port_name, ConnectionStatus::Disconnected)
-                    }
-                    ConnectionStatus::NotConnected => {
-                        print_status(port_name, ConnectionStatus::NotConnected)
+                    ConnectionStatus::Finished => {
+                        print_status(source.as_ref(), status);
+                        request_quit_with_state(&shared_state);
+                        break;
                     }
+                    status => print_status(source.as_ref(), status),
                 }
             }
-            None => {
+            Err(e) => {
                 if first_attempt {
-                    print_status(port_name, ConnectionStatus::NotConnected);
+                    if !matches!(e, SpewcapError::PortNotConnected) {
+                        print_error(&e.to_string());
+                    }
+                    print_status(source.as_ref(), ConnectionStatus::NotConnected);
                 }
                 sleep_ms(SERIAL_RETRY_DELAY.as_millis() as u64); // wait before retrying
             }
@@ -51,117 +110,559 @@ pub fn connect_loop(settings: Settings, shared_state: State) -> Result<()> {
     Ok(())
 }
 
-fn print_status(port_name: &str, status: ConnectionStatus) {
+fn print_status(source: &dyn Source, status: ConnectionStatus) {
+    let port_name = source.name();
     match status {
-        ConnectionStatus::Connected => {
-            print_message(format!("{} {}", port_name, "connected".green()))
-        }
+        ConnectionStatus::Connected => print_message(format!(
+            "{} {} ({})",
+            port_name,
+            "connected".green(),
+            source.summary()
+        )),
         ConnectionStatus::NotConnected => {
             print_message(format!("{} {}", port_name, "not connected".yellow()))
         }
         ConnectionStatus::Disconnected => {
             print_message(format!("{} {}", port_name, "disconnected".red()))
         }
+        ConnectionStatus::Closed => {
+            print_message(format!("{} {}", port_name, "closed".yellow()))
+        }
+        ConnectionStatus::Finished => {
+            print_message(format!("{} {}", port_name, "finished".green()))
+        }
     }
 }
 
-fn open_serial_port(port: &str, baud_rate: u32) -> Option<SerialPort> {
-    if let Err(e) = validation::validate_port_name(port) {
-        print_error(&format!("Invalid port: {e}"));
-        return None;
+/// Reports the input lines that changed since they were last read
+fn check_input_lines(source: &mut dyn Source, previous: &mut Option<InputLines>) {
+    let Some(lines) = source.input_lines() else {
+        return;
+    };
+    if let Some(changes) = previous.and_then(|previous| lines.changes(&previous)) {
+        print_message(format!("{} {}", source.name(), changes));
     }
-    if let Err(e) = validation::validate_baud_rate(baud_rate) {
-        print_error(&format!("Invalid baud rate: {e}"));
+    *previous = Some(lines);
+}
+
+fn take_pending_settings(shared_state: &State) -> Option<Settings> {
+    if !shared_state.reconnect_requested.swap(false, Ordering::Relaxed) {
         return None;
     }
-    SerialPortBuilder::new()
-        .baud_rate(baud_rate)
-        .read_timeout(Some(SERIAL_READ_TIMEOUT))  // timeout duration from constants
-        .open(port)
-        .ok()
+    match shared_state.pending_settings.lock() {
+        Ok(mut pending_settings) => pending_settings.take(),
+        Err(e) => {
+            print_error(&format!("Failed to acquire lock on pending settings: {e}"));
+            None
+        }
+    }
+}
+
+fn announce_source_change(old: &dyn Source, new: &dyn Source, shared_state: &State) {
+    let (message, log_message, description) = if old.name() == new.name() {
+        let description = format!("{} ({} -> {})", new.name(), old.summary(), new.summary());
+        ("Changing baud rate", "Baud rate changed", description)
+    } else {
+        let description = format!(
+            "{} ({}) -> {} ({})",
+            old.name(),
+            old.summary(),
+            new.name(),
+            new.summary()
+        );
+        ("Switching port", "Port changed", description)
+    };
+    print_message(format!("{}: {}", message, description));
+    write_to_active_log(shared_state, |log| {
+        log.write_line(&format!("--- {}: {} ---\n", log_message, description), Moment::now())
+    });
+}
+
+/// A local serial port opened with the configured line settings
+pub struct SerialSource {
+    settings: Settings,
+    port: Option<SerialPort>,
+}
+
+impl SerialSource {
+    pub fn new(settings: &Settings) -> Self {
+        SerialSource {
+            settings: settings.clone(),
+            port: None,
+        }
+    }
+}
+
+impl Source for SerialSource {
+    fn name(&self) -> String {
+        self.settings.port.clone()
+    }
+
+    fn summary(&self) -> String {
+        self.settings.line_summary()
+    }
+
+    fn open(&mut self) -> Result<()> {
+        let settings = &self.settings;
+        validation::validate_port_name(&settings.port)?;
+        validation::validate_baud_rate(settings.baud_rate)?;
+        let mut port = SerialPortBuilder::new()
+            .baud_rate(settings.baud_rate)
+            .data_bits(settings.data_bits)
+            .parity(settings.parity)
+            .stop_bits(settings.stop_bits)
+            .flow_control(settings.flow_control)
+            .read_timeout(Some(SERIAL_READ_TIMEOUT))  // timeout duration from constants
+            .write_timeout(Some(SERIAL_WRITE_TIMEOUT))
+            .open(&settings.port)
+            // the port may simply not be plugged in yet, so keep retrying quietly
+            .map_err(|_| SpewcapError::PortNotConnected)?;
+        if let Some(dtr) = settings.dtr {
+            port.write_data_terminal_ready(dtr)?;
+        }
+        if let Some(rts) = settings.rts {
+            port.write_request_to_send(rts)?;
+        }
+        self.port = Some(port);
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        self.port = None;
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> ReadResult {
+        match &mut self.port {
+            Some(port) => transport::read_result(port.read(buffer)),
+            None => ReadResult::Error,
+        }
+    }
+
+    fn writer(&self) -> Option<Box<dyn SourceWriter>> {
+        let port = self.port.as_ref()?.try_clone().ok()?;
+        Some(Box::new(port))
+    }
+
+    fn input_lines(&mut self) -> Option<InputLines> {
+        let port = self.port.as_mut()?;
+        let lines = InputLines {
+            cts: port.read_clear_to_send().ok(),
+            dsr: port.read_data_set_ready().ok(),
+            dcd: port.read_carrier_detect().ok(),
+            ri: port.read_ring_indicator().ok(),
+        };
+        // a line the port cannot read is left out rather than hiding the others
+        (lines != InputLines::default()).then_some(lines)
+    }
+}
+
+impl SourceWriter for SerialPort {
+    fn set_dtr(&mut self, level: bool) -> io::Result<()> {
+        Ok(self.write_data_terminal_ready(level)?)
+    }
+
+    fn set_rts(&mut self, level: bool) -> io::Result<()> {
+        Ok(self.write_request_to_send(level)?)
+    }
+
+    fn set_break(&mut self, on: bool) -> io::Result<()> {
+        if on {
+            Ok(SerialPort::set_break(self)?)
+        } else {
+            Ok(self.clear_break()?)
+        }
+    }
+}
+
+fn set_port_writer(shared_state: &State, writer: Option<Box<dyn SourceWriter>>) {
+    match shared_state.port_writer.lock() {
+        Ok(mut port_writer) => *port_writer = writer,
+        Err(e) => print_error(&format!("Failed to acquire lock on port writer: {e}")),
+    }
+}
+
+pub fn write_to_port(shared_state: &State, data: &[u8]) -> Result<()> {
+    let mut port_writer = shared_state
+        .port_writer
+        .lock()
+        .map_err(|e| SpewcapError::Transmit(format!("Failed to acquire lock on port writer: {e}")))?;
+    let port = port_writer.as_mut().ok_or(SpewcapError::PortNotConnected)?;
+    port.write_all(data)?;
+    port.flush()?;
+    Ok(())
 }
 
 fn read_loop<W: Write>(
-    mut port: SerialPort,
+    source: &mut dyn Source,
     shared_state: &State,
-    stdout: &mut W,
+    limits: &mut CaptureLimits,
+    output: &mut CaptureOutput<W>,
 ) -> ConnectionStatus {
-    let mut line_buffer = LineBuffer::new();
+    let mut line_buffer = LineBuffer::with_options(output.decoding, output.newline);
     let mut data_buffer = [0; SERIAL_READ_BUFFER_SIZE];
+    let mut last_data = Instant::now();
+    let mut input_lines = None;
+    let mut last_line_check = Instant::now();
     loop {
         if quit_requested(shared_state) {
             return ConnectionStatus::Connected;
         }
-        match read_data_from_port(&mut port, &mut data_buffer) {
+        if let Some(reason) = limits.check_timeout() {
+            stop_capture(shared_state, reason);
+            return ConnectionStatus::Connected;
+        }
+        if output.port_index == 0 && shared_state.reconnect_requested.load(Ordering::Relaxed) {
+            return ConnectionStatus::Closed;
+        }
+        if shared_state.port_suspended.load(Ordering::Relaxed) {
+            sleep_ms(SERIAL_NO_DATA_SLEEP.as_millis() as u64);
+            continue;
+        }
+        if last_line_check.elapsed() >= MODEM_POLL_INTERVAL {
+            last_line_check = Instant::now();
+            check_input_lines(source, &mut input_lines);
+        }
+        if output.port_index == 0 && xmodem::is_pending(shared_state) {
+            // the transfer reads the port itself, so end the line its banner left open
+            flush_partial_line(&mut line_buffer, limits, output, shared_state, false);
+            xmodem::run_pending(source, shared_state);
+        }
+        match source.read(&mut data_buffer) {
             ReadResult::Data(data_size) => {
-                process_received_data(&mut line_buffer, &data_buffer, data_size, stdout, shared_state);
+                let received_at = Moment::now();
+                last_data = Instant::now();
+                let chunk = &data_buffer[..data_size];
+                process_received_data(&mut line_buffer, chunk, received_at, limits, output, shared_state);
+            }
+            ReadResult::NoData => {
+                if idle_flush_due(&line_buffer, last_data, output.idle_flush) {
+                    flush_partial_line(&mut line_buffer, limits, output, shared_state, true);
+                }
+                sleep_ms(SERIAL_NO_DATA_SLEEP.as_millis() as u64)
+            }
+            ReadResult::End => {
+                flush_partial_line(&mut line_buffer, limits, output, shared_state, false);
+                return ConnectionStatus::Finished;
             }
-            ReadResult::NoData => sleep_ms(SERIAL_NO_DATA_SLEEP.as_millis() as u64),
             ReadResult::Error => return ConnectionStatus::Disconnected,
         }
     }
 }
 
-enum ReadResult {
-    Data(usize),
-    NoData,
-    Error,
+/// Per-connection rendering state for the console and log
+struct CaptureOutput<W: Write> {
+    stdout: W,
+    hex_dumper: HexDumper,
+    log_format: LogFormat,
+    decoding: Decoding,
+    idle_flush: Option<Duration>,
+    newline: NewlineMode,
+    timestamper: Option<Timestamper>,
+    timestamp_buffer: String,
+    at_line_start: bool,
+    /// An idle-flushed partial line kept back while a filter is set, so the filter decides on
+    /// the whole line once it ends
+    held_partial: Option<Line>,
+    highlights: Vec<HighlightRule>,
+    share_mode: Option<ShareMode>,
+    port_index: usize,
+    /// The port's label when capturing several ports, prefixed to its console lines as `console_label`
+    /// and to its lines in the merged log
+    label: Option<String>,
+    console_label: Option<String>,
 }
 
-fn read_data_from_port(port: &mut SerialPort, data_buffer: &mut [u8]) -> ReadResult {
-    match port.read(data_buffer) {
-        Ok(0) => ReadResult::NoData,
-        Ok(data_size) => ReadResult::Data(data_size),
-        Err(e) if e.kind() == io::ErrorKind::TimedOut => ReadResult::NoData,
-        Err(_) => ReadResult::Error,
+impl<W: Write> CaptureOutput<W> {
+    fn new(stdout: W, settings: &Settings, timestamp_start: Moment) -> Self {
+        CaptureOutput {
+            stdout,
+            hex_dumper: HexDumper::new(settings.hex_width),
+            log_format: settings.log_format,
+            decoding: settings.decoding,
+            idle_flush: settings.idle_flush,
+            newline: settings.newline,
+            timestamper: settings
+                .console_timestamps
+                .then(|| Timestamper::new(settings.timestamp_mode, settings.timestamp_format.clone(), timestamp_start)),
+            timestamp_buffer: String::with_capacity(TIMESTAMP_BUFFER_INITIAL_CAPACITY),
+            at_line_start: true,
+            held_partial: None,
+            highlights: settings.highlights.clone(),
+            share_mode: settings.share_address.map(|_| settings.share_mode),
+            port_index: settings.port_index,
+            label: settings.label.clone(),
+            console_label: settings.label.as_ref().map(|label| {
+                let color = settings.label_color.unwrap_or(PORT_LABEL_COLORS[0]);
+                format!("{} ", format!("[{label}]").color(color))
+            }),
+        }
+    }
+
+    fn write_console_line(&mut self, data: &[u8], received_at: Moment) -> io::Result<()> {
+        if self.at_line_start {
+            if let Some(timestamper) = &mut self.timestamper {
+                self.timestamp_buffer.clear();
+                timestamper
+                    .write_timestamp(&mut self.timestamp_buffer, received_at)
+                    .map_err(|e| io::Error::other(format!("Failed to write timestamp: {e}")))?;
+                write!(self.stdout, "[{}] ", self.timestamp_buffer)?;
+            }
+            if let Some(label) = &self.console_label {
+                self.stdout.write_all(label.as_bytes())?;
+            }
+        }
+        self.stdout.write_all(data)?;
+        self.at_line_start = data.ends_with(b"\n") || data.ends_with(b"\r");
+        // other ports print in between, so a partial line cannot be continued later
+        if self.console_label.is_some() && !self.at_line_start {
+            self.stdout.write_all(b"\n")?;
+            self.at_line_start = true;
+        }
+        Ok(())
     }
 }
 
 fn process_received_data<W: Write>(
     line_buffer: &mut LineBuffer,
-    data_buffer: &[u8],
-    data_size: usize,
-    stdout: &mut W,
+    data: &[u8],
+    received_at: Moment,
+    limits: &mut CaptureLimits,
+    output: &mut CaptureOutput<W>,
     shared_state: &State,
 ) {
-    line_buffer.write(data_buffer, data_size);
-    let lines_processed = process_complete_lines(line_buffer, stdout, shared_state);
-    if lines_processed > 0 {
-        flush_output(stdout);
+    line_buffer.write_received(data, data.len(), received_at);
+    if output.share_mode == Some(ShareMode::Raw) {
+        share::broadcast(shared_state, data);
+    }
+    if output.port_index == 0 {
+        send::tap_received(shared_state, data);
+    }
+    output_chunk(data, received_at, output, shared_state);
+    let lines_processed = process_complete_lines(line_buffer, limits, output, shared_state);
+    if lines_processed > 0 || hex_display_active(shared_state) {
+        flush_output(&mut output.stdout);
+    }
+    shared_state.bytes_received.fetch_add(data.len() as u64, Ordering::Relaxed);
+    if quit_requested(shared_state) {
+        return;
+    }
+    if let Some(reason) = limits.record_bytes(data.len()) {
+        stop_capture(shared_state, reason);
     }
 }
 
 fn process_complete_lines<W: Write>(
     line_buffer: &mut LineBuffer,
-    stdout: &mut W,
+    limits: &mut CaptureLimits,
+    output: &mut CaptureOutput<W>,
     shared_state: &State,
 ) -> usize {
     let mut lines_processed = 0;
-    while let Some(line) = line_buffer.next_line() {
-        output_line(&line, stdout, shared_state);
+    while let Some(line) = line_buffer.next_decoded_line() {
+        let stop_reason = limits.record_line(&line.text);
+        let line = complete_held_line(output, line);
+        output_line(&line, output, shared_state);
         lines_processed += 1;
+        if let Some(reason) = stop_reason {
+            flush_output(&mut output.stdout);
+            stop_capture(shared_state, reason);
+            break;
+        }
         // yield occasionally for very high throughput
         if lines_processed % HIGH_THROUGHPUT_YIELD_THRESHOLD == 0 {
-            flush_output(stdout);
+            flush_output(&mut output.stdout);
             std::thread::yield_now();
         }
     }
     lines_processed
 }
 
+fn idle_flush_due(line_buffer: &LineBuffer, last_data: Instant, idle_flush: Option<Duration>) -> bool {
+    match idle_flush {
+        Some(timeout) => !line_buffer.is_empty() && last_data.elapsed() >= timeout,
+        None => false,
+    }
+}
+
+/// Shows a line that has not been terminated yet (eg. `login: `) after the port goes quiet.
+/// With `hold_for_filters`, the line is held back instead while a filter is set, as the filter
+/// can only decide on the whole line.
+fn flush_partial_line<W: Write>(
+    line_buffer: &mut LineBuffer,
+    limits: &mut CaptureLimits,
+    output: &mut CaptureOutput<W>,
+    shared_state: &State,
+    hold_for_filters: bool,
+) {
+    let partial = line_buffer.take_partial();
+    let stop_reason = partial.as_ref().and_then(|partial| limits.record_partial(&partial.text));
+    let line = match partial {
+        Some(partial) => complete_held_line(output, partial),
+        None => match output.held_partial.take() {
+            Some(held) => held,
+            None => return,
+        },
+    };
+    if hold_for_filters && stop_reason.is_none() && filters_set(shared_state) {
+        output.held_partial = Some(line);
+        return;
+    }
+    output_line(&line, output, shared_state);
+    flush_output(&mut output.stdout);
+    if let Some(reason) = stop_reason {
+        stop_capture(shared_state, reason);
+    }
+}
+
+/// Prepends the partial line held back for the filters, if any, to the rest of its line
+fn complete_held_line<W: Write>(output: &mut CaptureOutput<W>, line: Line) -> Line {
+    let Some(held) = output.held_partial.take() else {
+        return line;
+    };
+    let mut bytes = BytesMut::from(&held.bytes[..]);
+    bytes.extend_from_slice(&line.bytes);
+    Line {
+        text: held.text + &line.text,
+        bytes: bytes.freeze(),
+        received_at: held.received_at,
+    }
+}
+
 fn flush_output<W: Write>(stdout: &mut W) {
     if let Err(e) = stdout.flush() {
         print_error(&format!("Failed to flush stdout: {e}"));
     }
 }
 
-fn output_line<W: Write>(line: &str, stdout: &mut W, shared_state: &State) {
+fn hex_display_active(shared_state: &State) -> bool {
+    shared_state.hex_display.load(Ordering::Relaxed)
+}
+
+/// Handles the byte-oriented outputs: the hex dump view and the hex/raw log formats
+fn output_chunk<W: Write>(data: &[u8], received_at: Moment, output: &mut CaptureOutput<W>, shared_state: &State) {
+    if shared_state.capture_paused.load(Ordering::Relaxed) {
+        output.hex_dumper.skip(data.len());
+        return;
+    }
+    let hex_display = hex_display_active(shared_state);
+    let rows = if hex_display || output.log_format == LogFormat::Hex {
+        output.hex_dumper.dump(data)
+    } else {
+        output.hex_dumper.skip(data.len());
+        Vec::new()
+    };
+    if hex_display {
+        for row in &rows {
+            let label = output.console_label.as_deref().unwrap_or("");
+            if let Err(e) = write!(output.stdout, "{label}{row}") {
+                print_error(&format!("Failed to write to stdout: {e}"));
+            }
+        }
+    }
+    match output.log_format {
+        LogFormat::Text => {}
+        LogFormat::Hex => write_to_logs(shared_state, output, |log, label| {
+            rows.iter().try_for_each(|row| log.write_line(&labeled_line(label, row), received_at))
+        }),
+        LogFormat::Raw => write_to_logs(shared_state, output, |log, _| log.write_raw(data)),
+    }
+}
+
+fn output_line<W: Write>(line: &Line, output: &mut CaptureOutput<W>, shared_state: &State) {
+    let raw = output.decoding == Decoding::Raw;
+    // share clients get every line, whatever the local pause and filters are
+    if output.share_mode == Some(ShareMode::Lines) {
+        let text = labeled_line(output.label.as_deref(), &line.text);
+        share::broadcast(shared_state, if raw { &line.bytes } else { text.as_bytes() });
+    }
     if shared_state.capture_paused.load(Ordering::Relaxed) {
         return;
     }
-    if let Err(e) = stdout.write_all(line.as_bytes()) {
-        print_error(&format!("Failed to write to stdout: {e}"));
+    let (show, log) = line_visibility(shared_state, &line.text);
+    if show && !hex_display_active(shared_state) {
+        // highlighting only touches the console; the log gets the text as received
+        let highlighted = if raw { None } else { highlight_line(&output.highlights, &line.text) };
+        let data = match &highlighted {
+            Some(text) => text.as_bytes(),
+            None if raw => &line.bytes[..],
+            None => line.text.as_bytes(),
+        };
+        if let Err(e) = output.write_console_line(data, line.received_at) {
+            print_error(&format!("Failed to write to stdout: {e}"));
+        }
+    }
+    if log && output.log_format == LogFormat::Text {
+        if raw {
+            write_to_logs(shared_state, output, |log, _| log.write_raw(&line.bytes));
+        } else {
+            write_to_logs(shared_state, output, |log, label| {
+                log.write_line(&labeled_line(label, &line.text), line.received_at)
+            });
+        }
     }
+}
+
+/// Whether the console and log filters let a line through
+fn line_visibility(shared_state: &State, text: &str) -> (bool, bool) {
+    match shared_state.filters.lock() {
+        Ok(filters) => (filters.console.allows(text), filters.log.allows(text)),
+        Err(e) => {
+            print_error(&format!("Failed to acquire lock on filters: {e}"));
+            (true, true)
+        }
+    }
+}
+
+fn filters_set(shared_state: &State) -> bool {
+    shared_state
+        .filters
+        .lock()
+        .is_ok_and(|filters| filters.console.is_set() || filters.log.is_set())
+}
+
+/// Prefixes a line of the merged log with its port's label, ending it so the next port's line
+/// starts on its own
+fn labeled_line<'a>(label: Option<&str>, text: &'a str) -> Cow<'a, str> {
+    match label {
+        Some(label) if text.ends_with(['\n', '\r']) => Cow::Owned(format!("[{label}] {text}")),
+        Some(label) => Cow::Owned(format!("[{label}] {text}\n")),
+        None => Cow::Borrowed(text),
+    }
+}
+
+/// Writes to the active (merged) log, passing the label to prefix, and to the port's own log if
+/// per-port logs are enabled
+fn write_to_logs<W: Write, F>(shared_state: &State, output: &CaptureOutput<W>, mut write: F)
+where
+    F: FnMut(&mut LogFile, Option<&str>) -> io::Result<()>,
+{
+    let mut log_state = match get_log_state(shared_state) {
+        Ok(state) => state,
+        Err(e) => {
+            print_error(&format!("Failed to acquire lock on log state during serial output: {e}"));
+            return;
+        }
+    };
+    let log_state = &mut *log_state;
+    let Some(log) = log_state.active_log.as_mut().filter(|log| log.is_enabled()) else {
+        return;
+    };
+    // the per-port logs are paused and resumed along with the merged log
+    let result = write(log, output.label.as_deref()).and_then(|_| {
+        match log_state.port_logs.get_mut(output.port_index) {
+            Some(port_log) => write(port_log, None),
+            None => Ok(()),
+        }
+    });
+    if let Err(e) = result {
+        print_error(&format!("Failed to write to log file: {e}"));
+    }
+}
+
+fn write_to_active_log<F>(shared_state: &State, write: F)
+where
+    F: FnOnce(&mut LogFile) -> io::Result<()>,
+{
     let mut log_state = match get_log_state(shared_state) {
         Ok(state) => state,
         Err(e) => {
@@ -171,9 +672,120 @@ fn output_line<W: Write>(line: &str, stdout: &mut W, shared_state: &State) {
     };
     if let Some(log) = &mut log_state.active_log {
         if log.is_enabled() {
-            if let Err(e) = log.write_line(line) {
+            if let Err(e) = write(log) {
                 print_error(&format!("Failed to write to log file: {e}"));
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::LineFilter;
+    use crate::state::init_state;
+    use regex::Regex;
+    use std::collections::VecDeque;
+
+    /// Replays canned chunks, an empty one being a read that timed out, then reports the
+    /// connection as lost
+    struct FakeSource {
+        chunks: VecDeque<&'static [u8]>,
+    }
+
+    impl Source for FakeSource {
+        fn name(&self) -> String {
+            "fake".to_string()
+        }
+
+        fn summary(&self) -> String {
+            "test data".to_string()
+        }
+
+        fn open(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn close(&mut self) {}
+
+        fn read(&mut self, buffer: &mut [u8]) -> ReadResult {
+            match self.chunks.pop_front() {
+                Some(b"") => ReadResult::NoData,
+                Some(chunk) => {
+                    buffer[..chunk.len()].copy_from_slice(chunk);
+                    ReadResult::Data(chunk.len())
+                }
+                None => ReadResult::Error,
+            }
+        }
+
+        fn writer(&self) -> Option<Box<dyn SourceWriter>> {
+            None
+        }
+    }
+
+    #[test]
+    fn test_read_loop_with_fake_source() {
+        let settings = Settings::for_tests("fake");
+        let shared_state = init_state();
+        let mut source = FakeSource {
+            chunks: VecDeque::from([&b"boot\nker"[..], b"nel\n", b"login: "]),
+        };
+        let mut limits = CaptureLimits::new(&settings);
+        let mut output = CaptureOutput::new(Vec::new(), &settings, Moment::now());
+        let status = read_loop(&mut source, &shared_state, &mut limits, &mut output);
+        assert!(matches!(status, ConnectionStatus::Disconnected));
+        assert_eq!(output.stdout, b"boot\nkernel\n");
+        assert_eq!(shared_state.bytes_received.load(Ordering::Relaxed), 19);
+    }
+
+    /// Captures `chunks` with an idle flush after every timed out read, through the console filter
+    fn capture_filtered(filter: LineFilter, chunks: &[&'static [u8]]) -> Vec<u8> {
+        let mut settings = Settings::for_tests("fake");
+        settings.idle_flush = Some(Duration::ZERO);
+        let shared_state = init_state();
+        shared_state.filters.lock().unwrap().console = filter;
+        let mut source = FakeSource {
+            chunks: chunks.iter().copied().collect(),
+        };
+        let mut limits = CaptureLimits::new(&settings);
+        let mut output = CaptureOutput::new(Vec::new(), &settings, Moment::now());
+        read_loop(&mut source, &shared_state, &mut limits, &mut output);
+        output.stdout
+    }
+
+    #[test]
+    fn test_filters_decide_on_the_whole_idle_flushed_line() {
+        let exclude = LineFilter {
+            include: None,
+            exclude: Some(Regex::new("^login").unwrap()),
+        };
+        assert_eq!(capture_filtered(exclude, &[b"login: ", b"", b"root\n", b"ok\n"]), b"ok\n");
+        let include = LineFilter {
+            include: Some(Regex::new("ERROR").unwrap()),
+            exclude: None,
+        };
+        let stdout = capture_filtered(include, &[b"ERR", b"", b"OR disk\n", b"fine\n"]);
+        assert_eq!(stdout, b"ERROR disk\n");
+    }
+
+    #[test]
+    fn test_idle_flush_shows_partial_line_without_filters() {
+        let stdout = capture_filtered(LineFilter::default(), &[b"login: ", b"", b"root\n"]);
+        assert_eq!(stdout, b"login: root\n");
+    }
+
+    #[test]
+    fn test_labeled_output_of_one_of_several_ports() {
+        let mut settings = Settings::for_tests("tcp://lab:2001");
+        settings.label = Some("modem".to_string());
+        let _colors = crate::utils::override_colors(false);
+        let mut output = CaptureOutput::new(Vec::new(), &settings, Moment::now());
+        output.write_console_line(b"AT\r\n", Moment::now()).unwrap();
+        output.write_console_line(b"login: ", Moment::now()).unwrap();
+        assert_eq!(output.stdout, b"[modem] AT\r\n[modem] login: \n");
+        assert_eq!(labeled_line(Some("modem"), "OK\n"), "[modem] OK\n");
+        assert_eq!(labeled_line(Some("modem"), "> "), "[modem] > \n");
+        assert_eq!(labeled_line(None, "> "), "> ");
+    }
+}
@@ -1,21 +1,183 @@
 use clap::Parser;
-use dialoguer::Select;
+use colored::Color;
+use dialoguer::{Input, Select};
+use regex::Regex;
 use serde::Deserialize;
-use serialport5::{available_ports, SerialPortType};
+use serialport5::{available_ports, DataBits, FlowControl, Parity, SerialPortType, StopBits};
+use std::collections::BTreeMap;
 use std::fs;
+use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::time::Duration;
 
-use crate::constants::DEFAULT_BAUD_RATE;
+use crate::buffer::{Decoding, NewlineMode};
+use crate::filter::{FilterChange, FilterKind, FilterTarget, Filters, LineFilter};
+use crate::hexdump::DisplayMode;
+use crate::highlight::HighlightRule;
+use crate::log::{LineEnding, LogFormat};
+use crate::macros::{Macro, MacroKey};
+use crate::modem::LineSequence;
+use crate::share::ShareMode;
+use crate::tcp;
+use crate::timestamp::TimestampMode;
+use crate::constants::*;
 use crate::utils;
 use crate::error::{Result, SpewcapError};
 use crate::validation;
 
+/// One of several ports captured in the same session
+#[derive(Clone, Debug, PartialEq)]
+pub struct PortSpec {
+    pub port: String,
+    pub baud_rate: u32,
+    pub label: String,
+    pub color: Color,
+}
+
 #[derive(Clone, Debug)]
 pub struct Settings {
     pub port: String,
     pub baud_rate: u32,
+    pub data_bits: DataBits,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+    pub flow_control: FlowControl,
+    pub dtr: Option<bool>,
+    pub rts: Option<bool>,
     pub timestamps: bool,
     pub log_folder: Option<PathBuf>,
+    pub log_name_template: String,
+    pub log_file: Option<PathBuf>,
+    pub headless: bool,
+    pub quiet: bool,
+    pub stop_after: Option<Duration>,
+    pub max_lines: Option<u64>,
+    pub max_bytes: Option<u64>,
+    pub stop_pattern: Option<Regex>,
+    pub display_mode: DisplayMode,
+    pub hex_width: usize,
+    pub log_format: LogFormat,
+    pub decoding: Decoding,
+    pub idle_flush: Option<Duration>,
+    pub newline: NewlineMode,
+    pub log_line_ending: LineEnding,
+    pub console_timestamps: bool,
+    pub timestamp_mode: TimestampMode,
+    pub timestamp_format: Option<String>,
+    pub highlights: Vec<HighlightRule>,
+    pub filters: Filters,
+    pub tui: bool,
+    pub scrollback_lines: usize,
+    pub share_address: Option<SocketAddr>,
+    pub share_mode: ShareMode,
+    pub share_input: bool,
+    pub replay: Option<PathBuf>,
+    pub replay_speed: f64,
+    /// Every port of the session, the first being `port`
+    pub ports: Vec<PortSpec>,
+    /// Which of `ports` these settings capture
+    pub port_index: usize,
+    /// Set when several ports are captured, to tell their output apart
+    pub label: Option<String>,
+    pub label_color: Option<Color>,
+    pub log_per_port: bool,
+    pub macros: Vec<Macro>,
+    pub line_sequences: Vec<LineSequence>,
+}
+
+impl Settings {
+    /// Short description of the line configuration, eg. "115200 8N1 RTS/CTS"
+    pub fn line_summary(&self) -> String {
+        let data_bits = match self.data_bits {
+            DataBits::Five => 5,
+            DataBits::Six => 6,
+            DataBits::Seven => 7,
+            DataBits::Eight => 8,
+        };
+        let parity = match self.parity {
+            Parity::None => 'N',
+            Parity::Odd => 'O',
+            Parity::Even => 'E',
+        };
+        let stop_bits = match self.stop_bits {
+            StopBits::One => 1,
+            StopBits::Two => 2,
+        };
+        let flow_control = match self.flow_control {
+            FlowControl::None => "",
+            FlowControl::Software => " XON/XOFF",
+            FlowControl::Hardware => " RTS/CTS",
+        };
+        format!("{} {}{}{}{}", self.baud_rate, data_bits, parity, stop_bits, flow_control)
+    }
+
+    pub fn is_multi_port(&self) -> bool {
+        self.ports.len() > 1
+    }
+
+    /// The settings for capturing one of the session's ports
+    pub fn for_port(&self, index: usize) -> Settings {
+        let mut settings = self.clone();
+        if let Some(spec) = self.ports.get(index).filter(|_| self.is_multi_port()) {
+            settings.port = spec.port.clone();
+            settings.baud_rate = spec.baud_rate;
+            settings.label = Some(spec.label.clone());
+            settings.label_color = Some(spec.color);
+            settings.port_index = index;
+        }
+        settings
+    }
+
+    /// Default settings for a port, without touching the config file or the system's ports
+    #[cfg(test)]
+    pub fn for_tests(port: &str) -> Self {
+        Settings {
+            port: port.to_string(),
+            baud_rate: DEFAULT_BAUD_RATE,
+            data_bits: DataBits::Eight,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+            flow_control: FlowControl::None,
+            dtr: None,
+            rts: None,
+            timestamps: false,
+            log_folder: None,
+            log_name_template: DEFAULT_LOG_NAME_TEMPLATE.to_string(),
+            log_file: None,
+            headless: false,
+            quiet: false,
+            stop_after: None,
+            max_lines: None,
+            max_bytes: None,
+            stop_pattern: None,
+            display_mode: DisplayMode::Text,
+            hex_width: DEFAULT_HEX_WIDTH,
+            log_format: LogFormat::Text,
+            decoding: Decoding::Lossy,
+            idle_flush: None,
+            newline: NewlineMode::Lf,
+            log_line_ending: LineEnding::Keep,
+            console_timestamps: false,
+            timestamp_mode: TimestampMode::Elapsed,
+            timestamp_format: None,
+            highlights: Vec::new(),
+            filters: Filters::default(),
+            tui: false,
+            scrollback_lines: DEFAULT_SCROLLBACK_LINES,
+            share_address: None,
+            share_mode: ShareMode::Lines,
+            share_input: false,
+            replay: None,
+            replay_speed: DEFAULT_REPLAY_SPEED,
+            ports: Vec::new(),
+            port_index: 0,
+            label: None,
+            label_color: None,
+            log_per_port: false,
+            macros: Vec::new(),
+            line_sequences: Vec::new(),
+        }
+    }
 }
 
 #[derive(Parser, Debug)]
@@ -28,6 +190,30 @@ pub struct Args {
     #[arg(short, long)]
     baud_rate: Option<u32>,
 
+    /// Data bits (5, 6, 7 or 8)
+    #[arg(long)]
+    data_bits: Option<u8>,
+
+    /// Parity (none, odd or even)
+    #[arg(long)]
+    parity: Option<String>,
+
+    /// Stop bits (1 or 2)
+    #[arg(long)]
+    stop_bits: Option<u8>,
+
+    /// Flow control (none, software or hardware)
+    #[arg(long)]
+    flow_control: Option<String>,
+
+    /// DTR level set when the port is opened (on or off)
+    #[arg(long)]
+    dtr: Option<String>,
+
+    /// RTS level set when the port is opened (on or off)
+    #[arg(long)]
+    rts: Option<String>,
+
     /// Prepend timestamps in log
     #[arg(short, long)]
     timestamps: bool,
@@ -40,6 +226,134 @@ pub struct Args {
     #[arg(short = 'f', long)]
     log_folder: Option<String>,
 
+    /// Log file name template ({port}, {baud}, {date}, {time}, {session})
+    #[arg(long)]
+    log_name: Option<String>,
+
+    /// Fixed log file path (the log is kept on exit)
+    #[arg(long)]
+    log_file: Option<String>,
+
+    /// Run without prompts or key commands (for CI and automation)
+    #[arg(long)]
+    headless: bool,
+
+    /// Do not print captured data to stdout
+    #[arg(short, long)]
+    quiet: bool,
+
+    /// Stop capturing after this many seconds (exit code 2)
+    #[arg(long, value_name = "SECONDS")]
+    stop_after: Option<u64>,
+
+    /// Stop capturing after this many lines (exit code 3)
+    #[arg(long)]
+    max_lines: Option<u64>,
+
+    /// Stop capturing after this many bytes (exit code 4)
+    #[arg(long)]
+    max_bytes: Option<u64>,
+
+    /// Stop capturing when a received line matches this regex (exit code 0)
+    #[arg(long, value_name = "REGEX")]
+    stop_on: Option<String>,
+
+    /// Start in hex dump display mode
+    #[arg(short = 'x', long)]
+    hex: bool,
+
+    /// Bytes per row in hex dump mode
+    #[arg(long)]
+    hex_width: Option<usize>,
+
+    /// Log format (text, hex or raw)
+    #[arg(long)]
+    log_format: Option<String>,
+
+    /// Decoding of non-UTF-8 bytes (lossy, escaped, latin1, cp437 or raw)
+    #[arg(long)]
+    decoding: Option<String>,
+
+    /// Show a partial line after this many milliseconds without new data (default: 0, disabled)
+    #[arg(long, value_name = "MS")]
+    idle_flush: Option<u64>,
+
+    /// Received line endings (lf, cr, crlf or any)
+    #[arg(long)]
+    newline: Option<String>,
+
+    /// Line ending written to the log (keep, lf or crlf)
+    #[arg(long)]
+    log_line_ending: Option<String>,
+
+    /// Prepend timestamps on the console
+    #[arg(long)]
+    console_timestamps: bool,
+
+    /// Timestamp mode (elapsed, local, utc or delta)
+    #[arg(long)]
+    timestamp_mode: Option<String>,
+
+    /// Timestamp format as a strftime-style string (eg. "%H:%M:%S%.3f")
+    #[arg(long)]
+    timestamp_format: Option<String>,
+
+    /// Only show lines matching this regex on the console
+    #[arg(long, value_name = "REGEX")]
+    console_include: Option<String>,
+
+    /// Hide lines matching this regex on the console
+    #[arg(long, value_name = "REGEX")]
+    console_exclude: Option<String>,
+
+    /// Only log lines matching this regex
+    #[arg(long, value_name = "REGEX")]
+    log_include: Option<String>,
+
+    /// Do not log lines matching this regex
+    #[arg(long, value_name = "REGEX")]
+    log_exclude: Option<String>,
+
+    /// Full-screen mode with scrollback, search and a status bar
+    #[arg(long)]
+    tui: bool,
+
+    /// Number of lines kept in the full-screen scrollback
+    #[arg(long, value_name = "LINES")]
+    scrollback: Option<u64>,
+
+    /// Share the capture with TCP clients on this port or ip:port
+    #[arg(long, value_name = "ADDRESS")]
+    share: Option<String>,
+
+    /// What share clients receive (lines or raw)
+    #[arg(long)]
+    share_mode: Option<String>,
+
+    /// Send what share clients type to the port
+    #[arg(long)]
+    share_input: bool,
+
+    /// Play a saved log or raw capture instead of opening a port
+    #[arg(long, value_name = "FILE")]
+    replay: Option<String>,
+
+    /// Replay speed factor (0 plays as fast as possible)
+    #[arg(long, value_name = "FACTOR")]
+    replay_speed: Option<f64>,
+
+    /// Another port to capture in the same session, as [LABEL=]PORT[@BAUD] (repeatable)
+    #[arg(long, value_name = "SPEC")]
+    extra_port: Vec<String>,
+
+    /// Label shown before the lines of the main port when capturing several ports
+    #[arg(long)]
+    label: Option<String>,
+
+    /// Also write a log file per port when capturing several ports
+    #[arg(long)]
+    log_per_port: bool,
+
     #[arg(long)]
     pub list: bool,
 }
@@ -48,11 +362,84 @@ pub struct Args {
 pub struct Config {
     port: Option<String>,
     baud_rate: Option<u32>,
+    data_bits: Option<u8>,
+    parity: Option<String>,
+    stop_bits: Option<u8>,
+    flow_control: Option<String>,
+    dtr: Option<bool>,
+    rts: Option<bool>,
     timestamps: Option<bool>,
     log_folder: Option<String>,
+    log_name_template: Option<String>,
+    log_file: Option<String>,
+    pub headless: Option<bool>,
+    quiet: Option<bool>,
+    stop_after: Option<u64>,
+    max_lines: Option<u64>,
+    max_bytes: Option<u64>,
+    stop_on: Option<String>,
+    display_mode: Option<String>,
+    hex_width: Option<usize>,
+    log_format: Option<String>,
+    decoding: Option<String>,
+    idle_flush_ms: Option<u64>,
+    newline: Option<String>,
+    log_line_ending: Option<String>,
+    console_timestamps: Option<bool>,
+    timestamp_mode: Option<String>,
+    timestamp_format: Option<String>,
+    console_include: Option<String>,
+    console_exclude: Option<String>,
+    log_include: Option<String>,
+    log_exclude: Option<String>,
+    tui: Option<bool>,
+    scrollback_lines: Option<u64>,
+    share: Option<String>,
+    share_mode: Option<String>,
+    share_input: Option<bool>,
+    replay: Option<String>,
+    replay_speed: Option<f64>,
+    label: Option<String>,
+    label_color: Option<String>,
+    log_per_port: Option<bool>,
     pub log_on_start: Option<bool>,
     // pub clear_on_start: Option<bool>,
     pub disable_welcome: Option<bool>,
+    #[serde(default)]
+    highlight: Vec<HighlightConfig>,
+    #[serde(default)]
+    extra_port: Vec<ExtraPortConfig>,
+    #[serde(default)]
+    macros: BTreeMap<String, String>,
+    #[serde(default)]
+    line_sequence: Vec<LineSequenceConfig>,
+}
+
+/// A `[[line_sequence]]` table from the config file
+#[derive(Deserialize, Debug)]
+struct LineSequenceConfig {
+    name: String,
+    key: String,
+    steps: String,
+}
+
+/// An `[[extra_port]]` table from the config file
+#[derive(Deserialize, Debug)]
+struct ExtraPortConfig {
+    port: String,
+    baud_rate: Option<u32>,
+    label: Option<String>,
+    color: Option<String>,
+}
+
+/// A `[[highlight]]` table from the config file
+#[derive(Deserialize, Debug)]
+struct HighlightConfig {
+    pattern: String,
+    foreground: Option<String>,
+    background: Option<String>,
+    bold: Option<bool>,
+    scope: Option<String>,
 }
 impl Config {
     fn load(file_path: PathBuf) -> Option<Self> {
@@ -63,16 +450,59 @@ impl Config {
     fn use_args(&mut self, args: Args) -> Result<()> {
         self.apply_port_arg(args.port)?;
         self.apply_baud_rate_arg(args.baud_rate)?;
+        self.apply_line_args(args.data_bits, args.parity, args.stop_bits, args.flow_control)?;
+        self.apply_modem_line_args(args.dtr, args.rts)?;
         self.apply_log_folder_arg(args.log_folder)?;
+        self.apply_log_name_arg(args.log_name)?;
+        self.apply_log_file_arg(args.log_file)?;
         self.apply_bool_args(args.timestamps, args.log_on_start);
+        self.apply_headless_args(args.headless, args.quiet);
+        self.apply_limit_args(args.stop_after, args.max_lines, args.max_bytes, args.stop_on)?;
+        self.apply_display_args(args.hex, args.hex_width, args.log_format)?;
+        self.apply_decoding_arg(args.decoding)?;
+        if let Some(idle_flush) = args.idle_flush {
+            self.idle_flush_ms = Some(idle_flush);
+        }
+        self.apply_newline_args(args.newline, args.log_line_ending)?;
+        self.apply_timestamp_args(args.console_timestamps, args.timestamp_mode, args.timestamp_format)?;
+        self.apply_filter_args(args.console_include, args.console_exclude, args.log_include, args.log_exclude)?;
+        self.apply_tui_args(args.tui, args.scrollback)?;
+        self.apply_share_args(args.share, args.share_mode, args.share_input)?;
+        self.apply_replay_args(args.replay, args.replay_speed)?;
+        self.apply_multi_port_args(args.extra_port, args.label, args.log_per_port)?;
         Ok(())
     }
     
+    pub fn is_headless(&self) -> bool {
+        self.headless.unwrap_or(false)
+    }
+    
+    /// Headless runs cannot prompt, so the port and baud rate must already be known
+    pub fn require_complete(&self) -> Result<()> {
+        if self.port.is_none() && !self.is_replay() {
+            return Err(SpewcapError::Settings("A port must be specified in headless mode".to_string()));
+        }
+        if self.baud_rate.is_none() && self.needs_baud_rate() {
+            return Err(SpewcapError::Settings("A baud rate must be specified in headless mode".to_string()));
+        }
+        Ok(())
+    }
+    
+    /// Raw TCP and telnet servers fix the line settings on their side, and replays fall back
+    /// to the default rate for pacing
+    fn needs_baud_rate(&self) -> bool {
+        !self.is_replay() && !self.port.as_deref().is_some_and(tcp::has_fixed_line_settings)
+    }
+    
+    fn is_replay(&self) -> bool {
+        self.replay.is_some()
+    }
+    
     pub fn select_missing(&mut self) -> Result<()> {
-        if self.port.is_none() {
+        if self.port.is_none() && !self.is_replay() {
             self.port = Some(select_port()?);
         }
-        if self.baud_rate.is_none() {
+        if self.baud_rate.is_none() && self.needs_baud_rate() {
             self.baud_rate = Some(select_baud_rate()?);
         }
         Ok(())
@@ -94,6 +524,42 @@ impl Config {
         Ok(())
     }
     
+    fn apply_line_args(
+        &mut self,
+        data_bits: Option<u8>,
+        parity: Option<String>,
+        stop_bits: Option<u8>,
+        flow_control: Option<String>,
+    ) -> Result<()> {
+        if let Some(data_bits) = data_bits {
+            validation::validate_data_bits(data_bits)?;
+            self.data_bits = Some(data_bits);
+        }
+        if let Some(parity) = parity {
+            validation::validate_parity(&parity)?;
+            self.parity = Some(parity);
+        }
+        if let Some(stop_bits) = stop_bits {
+            validation::validate_stop_bits(stop_bits)?;
+            self.stop_bits = Some(stop_bits);
+        }
+        if let Some(flow_control) = flow_control {
+            validation::validate_flow_control(&flow_control)?;
+            self.flow_control = Some(flow_control);
+        }
+        Ok(())
+    }
+    
+    fn apply_modem_line_args(&mut self, dtr: Option<String>, rts: Option<String>) -> Result<()> {
+        if let Some(dtr) = dtr {
+            self.dtr = Some(validation::validate_line_level(&dtr)?);
+        }
+        if let Some(rts) = rts {
+            self.rts = Some(validation::validate_line_level(&rts)?);
+        }
+        Ok(())
+    }
+    
     fn apply_log_folder_arg(&mut self, log_folder: Option<String>) -> Result<()> {
         if let Some(log_folder) = log_folder {
             validation::validate_directory_path(&log_folder)?;
@@ -102,13 +568,199 @@ impl Config {
         Ok(())
     }
     
+    fn apply_log_name_arg(&mut self, log_name: Option<String>) -> Result<()> {
+        if let Some(log_name) = log_name {
+            validation::validate_log_name_template(&log_name)?;
+            self.log_name_template = Some(log_name);
+        }
+        Ok(())
+    }
+    
+    fn apply_log_file_arg(&mut self, log_file: Option<String>) -> Result<()> {
+        if let Some(log_file) = log_file {
+            validation::validate_file_path(&log_file)?;
+            self.log_file = Some(log_file);
+        }
+        Ok(())
+    }
+    
+    fn apply_headless_args(&mut self, headless: bool, quiet: bool) {
+        if headless {
+            self.headless = Some(true);
+        }
+        if quiet {
+            self.quiet = Some(true);
+        }
+    }
+    
+    fn apply_limit_args(
+        &mut self,
+        stop_after: Option<u64>,
+        max_lines: Option<u64>,
+        max_bytes: Option<u64>,
+        stop_on: Option<String>,
+    ) -> Result<()> {
+        if let Some(stop_after) = stop_after {
+            self.stop_after = Some(validation::validate_limit("stop_after", stop_after)?);
+        }
+        if let Some(max_lines) = max_lines {
+            self.max_lines = Some(validation::validate_limit("max_lines", max_lines)?);
+        }
+        if let Some(max_bytes) = max_bytes {
+            self.max_bytes = Some(validation::validate_limit("max_bytes", max_bytes)?);
+        }
+        if let Some(stop_on) = stop_on {
+            validation::validate_regex(&stop_on)?;
+            self.stop_on = Some(stop_on);
+        }
+        Ok(())
+    }
+    
+    fn apply_display_args(
+        &mut self,
+        hex: bool,
+        hex_width: Option<usize>,
+        log_format: Option<String>,
+    ) -> Result<()> {
+        if hex {
+            self.display_mode = Some("hex".to_string());
+        }
+        if let Some(hex_width) = hex_width {
+            self.hex_width = Some(validation::validate_hex_width(hex_width)?);
+        }
+        if let Some(log_format) = log_format {
+            validation::validate_log_format(&log_format)?;
+            self.log_format = Some(log_format);
+        }
+        Ok(())
+    }
+    
+    fn apply_decoding_arg(&mut self, decoding: Option<String>) -> Result<()> {
+        if let Some(decoding) = decoding {
+            validation::validate_decoding(&decoding)?;
+            self.decoding = Some(decoding);
+        }
+        Ok(())
+    }
+    
+    fn apply_newline_args(&mut self, newline: Option<String>, log_line_ending: Option<String>) -> Result<()> {
+        if let Some(newline) = newline {
+            validation::validate_newline_mode(&newline)?;
+            self.newline = Some(newline);
+        }
+        if let Some(log_line_ending) = log_line_ending {
+            validation::validate_line_ending(&log_line_ending)?;
+            self.log_line_ending = Some(log_line_ending);
+        }
+        Ok(())
+    }
+    
+    fn apply_timestamp_args(
+        &mut self,
+        console_timestamps: bool,
+        timestamp_mode: Option<String>,
+        timestamp_format: Option<String>,
+    ) -> Result<()> {
+        if console_timestamps {
+            self.console_timestamps = Some(true);
+        }
+        if let Some(timestamp_mode) = timestamp_mode {
+            validation::validate_timestamp_mode(&timestamp_mode)?;
+            self.timestamp_mode = Some(timestamp_mode);
+        }
+        if let Some(timestamp_format) = timestamp_format {
+            let mode = validation::validate_timestamp_mode(
+                self.timestamp_mode.as_deref().unwrap_or(DEFAULT_TIMESTAMP_MODE),
+            )?;
+            self.timestamp_format = Some(validation::validate_timestamp_format(&timestamp_format, mode)?);
+        }
+        Ok(())
+    }
+    
+    fn apply_filter_args(
+        &mut self,
+        console_include: Option<String>,
+        console_exclude: Option<String>,
+        log_include: Option<String>,
+        log_exclude: Option<String>,
+    ) -> Result<()> {
+        for (slot, pattern) in [
+            (&mut self.console_include, console_include),
+            (&mut self.console_exclude, console_exclude),
+            (&mut self.log_include, log_include),
+            (&mut self.log_exclude, log_exclude),
+        ] {
+            if let Some(pattern) = pattern {
+                validation::validate_regex(&pattern)?;
+                *slot = Some(pattern);
+            }
+        }
+        Ok(())
+    }
+    
+    fn apply_tui_args(&mut self, tui: bool, scrollback: Option<u64>) -> Result<()> {
+        if tui {
+            self.tui = Some(true);
+        }
+        if let Some(scrollback) = scrollback {
+            self.scrollback_lines = Some(validation::validate_limit("scrollback_lines", scrollback)?);
+        }
+        Ok(())
+    }
+    
+    fn apply_share_args(&mut self, share: Option<String>, share_mode: Option<String>, share_input: bool) -> Result<()> {
+        if let Some(share) = share {
+            validation::validate_share_address(&share)?;
+            self.share = Some(share);
+        }
+        if let Some(share_mode) = share_mode {
+            validation::validate_share_mode(&share_mode)?;
+            self.share_mode = Some(share_mode);
+        }
+        if share_input {
+            self.share_input = Some(true);
+        }
+        Ok(())
+    }
+    
+    fn apply_replay_args(&mut self, replay: Option<String>, replay_speed: Option<f64>) -> Result<()> {
+        if let Some(replay) = replay {
+            validation::validate_replay_file(&replay)?;
+            self.replay = Some(replay);
+        }
+        if let Some(replay_speed) = replay_speed {
+            self.replay_speed = Some(validation::validate_replay_speed(replay_speed)?);
+        }
+        Ok(())
+    }
+    
+    fn apply_multi_port_args(&mut self, extra_ports: Vec<String>, label: Option<String>, log_per_port: bool) -> Result<()> {
+        for spec in extra_ports {
+            let (label, port, baud_rate) = validation::validate_port_spec(&spec)?;
+            self.extra_port.push(ExtraPortConfig {
+                port,
+                baud_rate,
+                label,
+                color: None,
+            });
+        }
+        if let Some(label) = label {
+            validation::validate_port_label(&label)?;
+            self.label = Some(label);
+        }
+        if log_per_port {
+            self.log_per_port = Some(true);
+        }
+        Ok(())
+    }
+    
     fn apply_bool_args(&mut self, timestamps: bool, log_on_start: bool) {
         self.timestamps = Some(timestamps);
         self.log_on_start = Some(log_on_start);
     }
 }
 
-fn select_port() -> Result<String> {
+pub fn select_port() -> Result<String> {
     let ports = available_ports().map_err(SpewcapError::SerialPort)?;
     if ports.is_empty() {
         return Err(SpewcapError::NoPortsFound);
@@ -139,7 +791,7 @@ fn select_port() -> Result<String> {
     Ok(port_names[selection].to_string())
 }
 
-fn select_baud_rate() -> Result<u32> {
+pub fn select_baud_rate() -> Result<u32> {
     let options = [
         4800, 9600, 19200, 38400, 57600, 115200, 230400, 460800, 921600,
     ];
@@ -156,6 +808,44 @@ fn select_baud_rate() -> Result<u32> {
     Ok(options[selection])
 }
 
+/// Asks which filter to change and for its new regex; an empty regex clears the filter
+pub fn select_filter() -> Result<FilterChange> {
+    let targets = [FilterTarget::Console, FilterTarget::Log, FilterTarget::Both];
+    let target = Select::new()
+        .with_prompt("Filter lines on")
+        .default(0)
+        .items(&["Console", "Log", "Console and log"])
+        .interact()
+        .map_err(|e| SpewcapError::Dialog(format!("No filter target selected: {e}")))?;
+    let kinds = [FilterKind::Include, FilterKind::Exclude];
+    let kind = Select::new()
+        .with_prompt("Filter type")
+        .default(0)
+        .items(&["Include (only show matching lines)", "Exclude (hide matching lines)"])
+        .interact()
+        .map_err(|e| SpewcapError::Dialog(format!("No filter type selected: {e}")))?;
+    let pattern: String = Input::new()
+        .with_prompt("Regex (empty to clear)")
+        .allow_empty(true)
+        .validate_with(|input: &String| -> std::result::Result<(), String> {
+            if input.is_empty() {
+                return Ok(());
+            }
+            validation::validate_regex(input).map(|_| ()).map_err(|e| e.to_string())
+        })
+        .interact_text()
+        .map_err(|e| SpewcapError::Dialog(format!("No filter entered: {e}")))?;
+    let pattern = match pattern.as_str() {
+        "" => None,
+        pattern => Some(validation::validate_regex(pattern)?),
+    };
+    Ok(FilterChange {
+        target: targets[target],
+        kind: kinds[kind],
+        pattern,
+    })
+}
+
 pub fn get_config(args: Args) -> Result<Config> {
     let mut config = load_config_from_files()?;
     config.use_args(args)?;
@@ -183,15 +873,126 @@ fn get_config_file_paths() -> Vec<PathBuf> {
 }
 
 pub fn get_settings(config: &Config) -> Result<Settings> {
-    let port = extract_and_validate_port(config)?;
+    let replay = config.replay.as_deref().map(validation::validate_replay_file).transpose()?;
+    // a replay stands in for the port, so its file name is used in messages and log names
+    let port = match &replay {
+        Some(path) => path.display().to_string(),
+        None => extract_and_validate_port(config)?,
+    };
     let baud_rate = extract_and_validate_baud_rate(config)?;
+    let data_bits = validation::validate_data_bits(config.data_bits.unwrap_or(DEFAULT_DATA_BITS))?;
+    let parity = validation::validate_parity(config.parity.as_deref().unwrap_or(DEFAULT_PARITY))?;
+    let stop_bits = validation::validate_stop_bits(config.stop_bits.unwrap_or(DEFAULT_STOP_BITS))?;
+    let flow_control = validation::validate_flow_control(
+        config.flow_control.as_deref().unwrap_or(DEFAULT_FLOW_CONTROL),
+    )?;
     let timestamps = config.timestamps.unwrap_or(false);
     let log_folder = extract_and_validate_log_folder(config)?;
+    let log_name_template = validation::validate_log_name_template(
+        config.log_name_template.as_deref().unwrap_or(DEFAULT_LOG_NAME_TEMPLATE),
+    )?;
+    let log_file = match &config.log_file {
+        Some(path) => Some(PathBuf::from(validation::validate_file_path(path)?)),
+        None => None,
+    };
+    let stop_after = match config.stop_after {
+        Some(seconds) => Some(Duration::from_secs(validation::validate_limit("stop_after", seconds)?)),
+        None => None,
+    };
+    let max_lines = config.max_lines.map(|n| validation::validate_limit("max_lines", n)).transpose()?;
+    let max_bytes = config.max_bytes.map(|n| validation::validate_limit("max_bytes", n)).transpose()?;
+    let stop_pattern = config.stop_on.as_deref().map(validation::validate_regex).transpose()?;
+    let display_mode = validation::validate_display_mode(
+        config.display_mode.as_deref().unwrap_or(DEFAULT_DISPLAY_MODE),
+    )?;
+    let hex_width = validation::validate_hex_width(config.hex_width.unwrap_or(DEFAULT_HEX_WIDTH))?;
+    let log_format = validation::validate_log_format(
+        config.log_format.as_deref().unwrap_or(DEFAULT_LOG_FORMAT),
+    )?;
+    let decoding = validation::validate_decoding(config.decoding.as_deref().unwrap_or(DEFAULT_DECODING))?;
+    let idle_flush = match config.idle_flush_ms.unwrap_or(DEFAULT_IDLE_FLUSH_MS) {
+        0 => None,
+        ms => Some(Duration::from_millis(ms)),
+    };
+    let newline = validation::validate_newline_mode(config.newline.as_deref().unwrap_or(DEFAULT_NEWLINE_MODE))?;
+    let log_line_ending = validation::validate_line_ending(
+        config.log_line_ending.as_deref().unwrap_or(DEFAULT_LOG_LINE_ENDING),
+    )?;
+    let timestamp_mode = validation::validate_timestamp_mode(
+        config.timestamp_mode.as_deref().unwrap_or(DEFAULT_TIMESTAMP_MODE),
+    )?;
+    let timestamp_format = config
+        .timestamp_format
+        .as_deref()
+        .map(|format| validation::validate_timestamp_format(format, timestamp_mode))
+        .transpose()?;
+    let highlights = extract_and_validate_highlights(config)?;
+    let scrollback_lines = match config.scrollback_lines {
+        Some(lines) => validation::validate_limit("scrollback_lines", lines)? as usize,
+        None => DEFAULT_SCROLLBACK_LINES,
+    };
+    let ports = extract_and_validate_ports(config, &port, baud_rate)?;
+    let tui = config.tui.unwrap_or(false) && !config.is_headless();
+    let (macros, line_sequences) = extract_and_validate_key_bindings(config, tui)?;
+    let replay_speed = validation::validate_replay_speed(config.replay_speed.unwrap_or(DEFAULT_REPLAY_SPEED))?;
+    let share_address = config.share.as_deref().map(validation::validate_share_address).transpose()?;
+    let share_mode = validation::validate_share_mode(config.share_mode.as_deref().unwrap_or(DEFAULT_SHARE_MODE))?;
+    let filters = Filters {
+        console: LineFilter {
+            include: config.console_include.as_deref().map(validation::validate_regex).transpose()?,
+            exclude: config.console_exclude.as_deref().map(validation::validate_regex).transpose()?,
+        },
+        log: LineFilter {
+            include: config.log_include.as_deref().map(validation::validate_regex).transpose()?,
+            exclude: config.log_exclude.as_deref().map(validation::validate_regex).transpose()?,
+        },
+    };
     Ok(Settings {
         port,
         baud_rate,
+        data_bits,
+        parity,
+        stop_bits,
+        flow_control,
+        dtr: config.dtr,
+        rts: config.rts,
         timestamps,
         log_folder,
+        log_name_template,
+        log_file,
+        headless: config.is_headless(),
+        quiet: config.quiet.unwrap_or(false),
+        stop_after,
+        max_lines,
+        max_bytes,
+        stop_pattern,
+        display_mode,
+        hex_width,
+        log_format,
+        decoding,
+        idle_flush,
+        newline,
+        log_line_ending,
+        console_timestamps: config.console_timestamps.unwrap_or(false),
+        timestamp_mode,
+        timestamp_format,
+        highlights,
+        filters,
+        // headless runs have no terminal to draw on
+        tui,
+        scrollback_lines,
+        share_address,
+        share_mode,
+        share_input: config.share_input.unwrap_or(false),
+        replay,
+        replay_speed,
+        ports,
+        port_index: 0,
+        label: None,
+        label_color: None,
+        log_per_port: config.log_per_port.unwrap_or(false),
+        macros,
+        line_sequences,
     })
 }
 
@@ -202,11 +1003,102 @@ fn extract_and_validate_port(config: &Config) -> Result<String> {
 }
 
 fn extract_and_validate_baud_rate(config: &Config) -> Result<u32> {
-    let baud_rate = config.baud_rate
-        .ok_or_else(|| SpewcapError::Settings("Could not set baud rate".to_string()))?;
+    let baud_rate = match config.baud_rate {
+        Some(baud_rate) => baud_rate,
+        None if !config.needs_baud_rate() => DEFAULT_BAUD_RATE,
+        None => return Err(SpewcapError::Settings("Could not set baud rate".to_string())),
+    };
     validation::validate_baud_rate(baud_rate)
 }
 
+/// The main port followed by the extra ports, labelled by their names unless given a label
+fn extract_and_validate_ports(config: &Config, port: &str, baud_rate: u32) -> Result<Vec<PortSpec>> {
+    let main_port = PortSpec {
+        port: port.to_string(),
+        baud_rate,
+        label: match &config.label {
+            Some(label) => validation::validate_port_label(label)?,
+            None => crate::log::sanitize_port_name(port),
+        },
+        color: match &config.label_color {
+            Some(color) => validation::validate_color(color)?,
+            None => PORT_LABEL_COLORS[0],
+        },
+    };
+    let mut ports = vec![main_port];
+    for (index, extra) in config.extra_port.iter().enumerate() {
+        let index = index + 1;
+        ports.push(PortSpec {
+            port: validation::validate_port_name(&extra.port)?,
+            // extra ports default to the main port's baud rate
+            baud_rate: validation::validate_baud_rate(extra.baud_rate.unwrap_or(baud_rate))?,
+            label: match &extra.label {
+                Some(label) => validation::validate_port_label(label)?,
+                None => crate::log::sanitize_port_name(&extra.port),
+            },
+            color: match &extra.color {
+                Some(color) => validation::validate_color(color)?,
+                None => PORT_LABEL_COLORS[index % PORT_LABEL_COLORS.len()],
+            },
+        });
+    }
+    if let Some(duplicate) = ports
+        .iter()
+        .enumerate()
+        .find_map(|(index, spec)| ports[..index].iter().find(|other| other.label == spec.label))
+    {
+        return Err(SpewcapError::InvalidPortLabel(format!("{} is used by more than one port", duplicate.label)));
+    }
+    Ok(ports)
+}
+
+/// Macros and line sequences share the same keys, so each key may be bound once. The
+/// full-screen view uses `F3` for search, so it cannot be bound there.
+fn extract_and_validate_key_bindings(config: &Config, tui: bool) -> Result<(Vec<Macro>, Vec<LineSequence>)> {
+    let mut macros = config
+        .macros
+        .iter()
+        .map(|(key, text)| validation::validate_macro(key, text))
+        .collect::<Result<Vec<_>>>()?;
+    macros.sort_by_key(|m| m.key);
+    let line_sequences = config
+        .line_sequence
+        .iter()
+        .map(|sequence| validation::validate_line_sequence(&sequence.name, &sequence.key, &sequence.steps))
+        .collect::<Result<Vec<_>>>()?;
+    let mut keys: Vec<_> = macros
+        .iter()
+        .map(|m| m.key)
+        .chain(line_sequences.iter().map(|sequence| sequence.key))
+        .collect();
+    keys.sort();
+    if let Some(duplicate) = keys.windows(2).find(|pair| pair[0] == pair[1]) {
+        return Err(SpewcapError::InvalidMacroKey(format!("{} is bound more than once", duplicate[0])));
+    }
+    if tui && keys.contains(&MacroKey::Function(3)) {
+        return Err(SpewcapError::InvalidMacroKey("F3 finds the next search match in full-screen mode".to_string()));
+    }
+    Ok((macros, line_sequences))
+}
+
+fn extract_and_validate_highlights(config: &Config) -> Result<Vec<HighlightRule>> {
+    config
+        .highlight
+        .iter()
+        .map(|highlight| {
+            Ok(HighlightRule {
+                pattern: validation::validate_regex(&highlight.pattern)?,
+                foreground: highlight.foreground.as_deref().map(validation::validate_color).transpose()?,
+                background: highlight.background.as_deref().map(validation::validate_color).transpose()?,
+                bold: highlight.bold.unwrap_or(false),
+                scope: validation::validate_highlight_scope(
+                    highlight.scope.as_deref().unwrap_or(DEFAULT_HIGHLIGHT_SCOPE),
+                )?,
+            })
+        })
+        .collect()
+}
+
 fn extract_and_validate_log_folder(config: &Config) -> Result<Option<PathBuf>> {
     match &config.log_folder {
         Some(folder_str) => {
@@ -216,3 +1108,15 @@ fn extract_and_validate_log_folder(config: &Config) -> Result<Option<PathBuf>> {
         None => Ok(None),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_f3_binding_rejected_in_full_screen_mode() {
+        let config: Config = toml::from_str("[macros]\nF3 = 'reboot\\r'\n").unwrap();
+        assert!(extract_and_validate_key_bindings(&config, false).is_ok());
+        assert!(extract_and_validate_key_bindings(&config, true).is_err());
+    }
+}
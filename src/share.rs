@@ -0,0 +1,246 @@
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::thread::JoinHandle;
+
+use crate::constants::*;
+use crate::error::{Result, SpewcapError};
+use crate::serial;
+use crate::settings::Settings;
+use crate::state::State;
+use crate::utils::{print_error, print_message, print_warning, quit_requested, sleep_ms};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShareMode {
+    /// Complete lines, decoded the same way as the console
+    Lines,
+    /// Received bytes, untouched
+    Raw,
+}
+
+/// A teammate watching the port over TCP
+pub struct ShareClient {
+    address: SocketAddr,
+    stream: TcpStream,
+    /// Data waiting for the client's writer thread, so a slow client never holds up the capture
+    queue: SyncSender<Vec<u8>>,
+}
+
+/// Starts listening for share clients if sharing is enabled
+pub fn start(settings: &Settings, shared_state: &State) -> Result<Option<JoinHandle<Result<()>>>> {
+    let Some(address) = settings.share_address else {
+        return Ok(None);
+    };
+    let listener = bind(address)?;
+    let mode = match settings.share_mode {
+        ShareMode::Lines => "lines",
+        ShareMode::Raw => "raw",
+    };
+    let access = if settings.share_input { "input allowed" } else { "read-only" };
+    print_message(format!("Sharing {} on {} ({mode}, {access})", settings.port, listener.local_addr()?));
+    if settings.share_input && !address.ip().is_loopback() {
+        print_warning(&format!(
+            "Anyone who can reach {address} can write to {} without authentication; \
+             share on 127.0.0.1 to keep input local",
+            settings.port
+        ));
+    }
+    let input = settings.share_input;
+    let state = shared_state.clone();
+    Ok(Some(std::thread::spawn(move || accept_loop(listener, input, state))))
+}
+
+fn bind(address: SocketAddr) -> Result<TcpListener> {
+    let listener = TcpListener::bind(address)
+        .map_err(|e| SpewcapError::Connection(format!("Cannot listen on {address}: {e}")))?;
+    // polled so the thread notices a quit request
+    listener.set_nonblocking(true)?;
+    Ok(listener)
+}
+
+fn accept_loop(listener: TcpListener, input: bool, shared_state: State) -> Result<()> {
+    while !quit_requested(&shared_state) {
+        match listener.accept() {
+            Ok((stream, address)) => {
+                if let Err(e) = add_client(stream, address, input, &shared_state) {
+                    print_error(&format!("Failed to accept share client {address}: {e}"));
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => sleep_ms(SHARE_POLL_INTERVAL_MS),
+            Err(e) => {
+                print_error(&format!("Failed to accept share client: {e}"));
+                sleep_ms(SHARE_POLL_INTERVAL_MS);
+            }
+        }
+    }
+    disconnect_all(&shared_state);
+    Ok(())
+}
+
+fn add_client(stream: TcpStream, address: SocketAddr, input: bool, shared_state: &State) -> Result<()> {
+    // accepted sockets inherit the listener's non-blocking mode on some platforms
+    stream.set_nonblocking(false)?;
+    stream.set_nodelay(true)?;
+    stream.set_read_timeout(Some(SHARE_POLL_INTERVAL))?;
+    let reader = stream.try_clone()?;
+    let writer = stream.try_clone()?;
+    let (queue, queued) = mpsc::sync_channel(SHARE_QUEUE_LENGTH);
+    let client_count = {
+        let mut clients = shared_state
+            .share_clients
+            .lock()
+            .map_err(|e| SpewcapError::Connection(format!("Failed to acquire lock on share clients: {e}")))?;
+        clients.push(ShareClient { address, stream, queue });
+        clients.len()
+    };
+    print_message(format!("Share client {address} connected ({client_count} connected)"));
+    let state = shared_state.clone();
+    std::thread::spawn(move || client_loop(reader, address, input, state));
+    std::thread::spawn(move || write_loop(writer, queued));
+    Ok(())
+}
+
+/// Writes queued data to a client until it is dropped or the connection fails
+fn write_loop(mut writer: TcpStream, queued: Receiver<Vec<u8>>) {
+    for data in queued {
+        if writer.write_all(&data).is_err() {
+            // also ends the client's reader thread, which removes the client
+            let _ = writer.shutdown(Shutdown::Both);
+            break;
+        }
+    }
+}
+
+/// Reads from a client until it disconnects, forwarding its input to the port if allowed
+fn client_loop(mut reader: TcpStream, address: SocketAddr, input: bool, shared_state: State) {
+    let mut buffer = [0; SERIAL_READ_BUFFER_SIZE];
+    while !quit_requested(&shared_state) {
+        match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(size) if input => {
+                if let Err(e) = serial::write_to_port(&shared_state, &buffer[..size]) {
+                    print_error(&format!("Failed to send input from share client {address}: {e}"));
+                }
+            }
+            Ok(_) => {}
+            Err(e) if matches!(e.kind(), io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock) => {}
+            Err(_) => break,
+        }
+    }
+    if remove_client(&shared_state, address) {
+        print_message(format!("Share client {address} disconnected"));
+    }
+}
+
+/// Returns whether the client was still connected
+fn remove_client(shared_state: &State, address: SocketAddr) -> bool {
+    let Ok(mut clients) = shared_state.share_clients.lock() else {
+        return false;
+    };
+    let count = clients.len();
+    clients.retain(|client| client.address != address);
+    clients.len() != count
+}
+
+fn disconnect_all(shared_state: &State) {
+    if let Ok(mut clients) = shared_state.share_clients.lock() {
+        for client in clients.drain(..) {
+            let _ = client.stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+/// Queues data for every connected client, dropping the ones whose queue is full
+pub fn broadcast(shared_state: &State, data: &[u8]) {
+    let mut clients = match shared_state.share_clients.lock() {
+        Ok(clients) => clients,
+        Err(e) => {
+            print_error(&format!("Failed to acquire lock on share clients: {e}"));
+            return;
+        }
+    };
+    let mut dropped = Vec::new();
+    clients.retain(|client| {
+        match client.queue.try_send(data.to_vec()) {
+            Ok(()) => return true,
+            // the writer thread already ended the connection
+            Err(TrySendError::Disconnected(_)) => return false,
+            Err(TrySendError::Full(_)) => {}
+        }
+        // also ends the client's reader and writer threads
+        let _ = client.stream.shutdown(Shutdown::Both);
+        dropped.push(client.address);
+        false
+    });
+    drop(clients);
+    for address in dropped {
+        print_message(format!("Share client {address} disconnected"));
+    }
+}
+
+/// Addresses of the connected clients, for the help message
+pub fn client_addresses(shared_state: &State) -> Vec<SocketAddr> {
+    match shared_state.share_clients.lock() {
+        Ok(clients) => clients.iter().map(|client| client.address).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::init_state;
+    use std::sync::atomic::Ordering;
+
+    #[test]
+    fn test_clients_receive_broadcast_until_they_disconnect() {
+        let state = init_state();
+        let listener = bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let address = listener.local_addr().unwrap();
+        let accept_state = state.clone();
+        let server = std::thread::spawn(move || accept_loop(listener, false, accept_state));
+
+        let mut client = TcpStream::connect(address).unwrap();
+        while client_addresses(&state).is_empty() {
+            sleep_ms(SHARE_POLL_INTERVAL_MS);
+        }
+        broadcast(&state, b"booting\n");
+        let mut received = [0; 8];
+        client.read_exact(&mut received).unwrap();
+        assert_eq!(&received, b"booting\n");
+
+        drop(client);
+        while !client_addresses(&state).is_empty() {
+            sleep_ms(SHARE_POLL_INTERVAL_MS);
+        }
+        state.quit_requested.store(true, Ordering::Relaxed);
+        server.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_stalled_client_is_dropped_without_blocking() {
+        let state = init_state();
+        let listener = bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let address = listener.local_addr().unwrap();
+        let accept_state = state.clone();
+        let server = std::thread::spawn(move || accept_loop(listener, false, accept_state));
+
+        // never reads, so its socket buffers and then its queue fill up
+        let _client = TcpStream::connect(address).unwrap();
+        while client_addresses(&state).is_empty() {
+            sleep_ms(SHARE_POLL_INTERVAL_MS);
+        }
+        let chunk = vec![b'x'; 65536];
+        let started = std::time::Instant::now();
+        let mut broadcasts = 0;
+        while !client_addresses(&state).is_empty() && broadcasts < 10_000 {
+            broadcast(&state, &chunk);
+            broadcasts += 1;
+        }
+        assert!(client_addresses(&state).is_empty());
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+
+        state.quit_requested.store(true, Ordering::Relaxed);
+        server.join().unwrap().unwrap();
+    }
+}
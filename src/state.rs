@@ -1,15 +1,55 @@
+use crate::filter::Filters;
+use crate::hexdump::DisplayMode;
+use crate::limits::StopReason;
 use crate::log::LogFile;
-use std::sync::atomic::AtomicBool;
+use crate::settings::Settings;
+use crate::share::ShareClient;
+use crate::timestamp::Moment;
+use crate::transport::SourceWriter;
+use crate::xmodem::TransferRequest;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 
 pub struct SharedState {
     pub capture_paused: AtomicBool,
     pub quit_requested: AtomicBool,
+    pub input_mode: AtomicBool,
+    pub hex_display: AtomicBool,
+    pub port_suspended: AtomicBool,
+    pub reconnect_requested: AtomicBool,
+    pub connected: AtomicBool,
+    pub bytes_received: AtomicU64,
     pub log_state: Mutex<LogState>,
+    pub port_writer: Mutex<Option<Box<dyn SourceWriter>>>,
+    pub pending_settings: Mutex<Option<Settings>>,
+    pub stop_reason: Mutex<Option<StopReason>>,
+    pub filters: Mutex<Filters>,
+    pub share_clients: Mutex<Vec<ShareClient>>,
+    pub replay_paused: AtomicBool,
+    /// Pending relative seek in milliseconds, consumed by the replay source
+    pub replay_seek_ms: AtomicI64,
+    /// Set while a file is being sent to the port, or a transfer is queued or running
+    pub transfer_active: AtomicBool,
+    pub transfer_cancel: AtomicBool,
+    /// Output of the main port, collected while a transfer waits for an echo or prompt
+    pub received_tap: Mutex<Option<Vec<u8>>>,
+    /// An XMODEM/YMODEM transfer waiting for the main port's capture thread to run it
+    pub pending_transfer: Mutex<Option<TransferRequest>>,
+    /// Levels last set on the main port's DTR and RTS lines
+    pub dtr_level: AtomicBool,
+    pub rts_level: AtomicBool,
+    /// Set while a line sequence runs on its own thread
+    pub sequence_active: AtomicBool,
+    pub sequence_cancel: AtomicBool,
+    /// Common base for the console timestamps of every port in the session
+    pub capture_start: Moment,
 }
 
 pub struct LogState {
     pub active_log: Option<LogFile>,
+    /// One log per port next to the merged `active_log`, indexed like `Settings::ports`
+    pub port_logs: Vec<LogFile>,
+    pub session_count: usize,
 }
 
 pub type State = Arc<SharedState>;
@@ -18,6 +58,49 @@ pub fn init_state() -> State {
     Arc::new(SharedState {
         capture_paused: AtomicBool::new(false),
         quit_requested: AtomicBool::new(false),
-        log_state: Mutex::new(LogState { active_log: None }),
+        input_mode: AtomicBool::new(false),
+        hex_display: AtomicBool::new(false),
+        port_suspended: AtomicBool::new(false),
+        reconnect_requested: AtomicBool::new(false),
+        connected: AtomicBool::new(false),
+        bytes_received: AtomicU64::new(0),
+        log_state: Mutex::new(LogState {
+            active_log: None,
+            port_logs: Vec::new(),
+            session_count: 0,
+        }),
+        port_writer: Mutex::new(None),
+        pending_settings: Mutex::new(None),
+        stop_reason: Mutex::new(None),
+        filters: Mutex::new(Filters::default()),
+        share_clients: Mutex::new(Vec::new()),
+        replay_paused: AtomicBool::new(false),
+        replay_seek_ms: AtomicI64::new(0),
+        transfer_active: AtomicBool::new(false),
+        transfer_cancel: AtomicBool::new(false),
+        received_tap: Mutex::new(None),
+        pending_transfer: Mutex::new(None),
+        dtr_level: AtomicBool::new(true),
+        rts_level: AtomicBool::new(true),
+        sequence_active: AtomicBool::new(false),
+        sequence_cancel: AtomicBool::new(false),
+        capture_start: Moment::now(),
     })
 }
+
+impl LogState {
+    /// The merged log and the per-port logs
+    pub fn logs_mut(&mut self) -> impl Iterator<Item = &mut LogFile> {
+        self.active_log.iter_mut().chain(self.port_logs.iter_mut())
+    }
+}
+
+/// Seeds the runtime-toggleable state from the startup settings
+pub fn apply_settings(state: &State, settings: &Settings) {
+    state
+        .hex_display
+        .store(settings.display_mode == DisplayMode::Hex, Ordering::Relaxed);
+    if let Ok(mut filters) = state.filters.lock() {
+        *filters = settings.filters.clone();
+    }
+}
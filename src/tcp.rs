@@ -0,0 +1,375 @@
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::constants::*;
+use crate::error::{Result, SpewcapError};
+use crate::modem::InputLines;
+use crate::rfc2217::{self, ServerReply, COM_PORT_OPTION};
+use crate::settings::Settings;
+use crate::telnet::{self, TelnetEvent, TelnetParser, DO, DONT, IAC, OPTION_BINARY, WILL, WONT};
+use crate::transport::{self, ReadResult, Source, SourceWriter};
+use crate::utils::print_warning;
+
+const TCP_SCHEME: &str = "tcp://";
+const TELNET_SCHEME: &str = "telnet://";
+const RFC2217_SCHEME: &str = "rfc2217://";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TcpProtocol {
+    /// Bytes are passed through untouched
+    Raw,
+    /// Telnet commands are stripped and answered, as with ser2net's `telnet` mode
+    Telnet,
+    /// Telnet with COM-PORT-OPTION, so the line settings are applied on the server's port
+    Rfc2217,
+}
+
+impl TcpProtocol {
+    fn uses_telnet(&self) -> bool {
+        *self != TcpProtocol::Raw
+    }
+}
+
+/// A serial-over-network server given as `tcp://host:port`, `telnet://host:port` or
+/// `rfc2217://host:port`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TcpEndpoint {
+    pub protocol: TcpProtocol,
+    pub host: String,
+    pub port: u16,
+}
+
+impl TcpEndpoint {
+    /// Returns `None` if the name is not a network URL (ie. it is a local port name)
+    pub fn parse(url: &str) -> Option<Result<Self>> {
+        let (protocol, address) = if let Some(address) = url.strip_prefix(TCP_SCHEME) {
+            (TcpProtocol::Raw, address)
+        } else if let Some(address) = url.strip_prefix(TELNET_SCHEME) {
+            (TcpProtocol::Telnet, address)
+        } else if let Some(address) = url.strip_prefix(RFC2217_SCHEME) {
+            (TcpProtocol::Rfc2217, address)
+        } else {
+            return None;
+        };
+        let invalid = || SpewcapError::InvalidPort(format!("{url} (expected eg. {TCP_SCHEME}host:port)"));
+        let endpoint = address
+            .rsplit_once(':')
+            .and_then(|(host, port)| {
+                let host = host.trim_start_matches('[').trim_end_matches(']');
+                let port = port.parse::<u16>().ok().filter(|&port| port != 0)?;
+                (!host.is_empty()).then(|| TcpEndpoint {
+                    protocol,
+                    host: host.to_string(),
+                    port,
+                })
+            })
+            .ok_or_else(invalid);
+        Some(endpoint)
+    }
+
+    fn connect(&self) -> Result<TcpStream> {
+        let addresses = (self.host.as_str(), self.port)
+            .to_socket_addrs()
+            .map_err(|e| SpewcapError::Connection(format!("Cannot resolve {}: {e}", self.host)))?;
+        let mut last_error = None;
+        for address in addresses {
+            match TcpStream::connect_timeout(&address, TCP_CONNECT_TIMEOUT) {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        let reason = last_error.map_or_else(|| "no addresses found".to_string(), |e| e.to_string());
+        Err(SpewcapError::Connection(format!("Cannot connect to {}:{}: {reason}", self.host, self.port)))
+    }
+}
+
+/// Whether the server fixes the line settings itself (raw TCP and plain telnet)
+pub fn has_fixed_line_settings(port: &str) -> bool {
+    matches!(
+        TcpEndpoint::parse(port),
+        Some(Ok(TcpEndpoint { protocol: TcpProtocol::Raw | TcpProtocol::Telnet, .. }))
+    )
+}
+
+/// A TCP connection to a serial server, read and written like a local port
+pub struct TcpSource {
+    settings: Settings,
+    endpoint: TcpEndpoint,
+    stream: Option<TcpStream>,
+    telnet: TelnetParser,
+    events: Vec<TelnetEvent>,
+    /// The last NOTIFY-MODEMSTATE sent by an RFC 2217 server
+    modem_state: Option<u8>,
+    /// Whether the server asked us to transmit in binary, so a bare CR is sent as is. Whether
+    /// the server transmits in binary is tracked by `telnet`.
+    binary: Arc<AtomicBool>,
+}
+
+impl TcpSource {
+    pub fn new(settings: &Settings, endpoint: TcpEndpoint) -> Self {
+        TcpSource {
+            settings: settings.clone(),
+            endpoint,
+            stream: None,
+            telnet: TelnetParser::new(),
+            events: Vec::new(),
+            modem_state: None,
+            binary: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    fn answer_telnet_events(&mut self) -> io::Result<()> {
+        let Some(stream) = &mut self.stream else {
+            return Ok(());
+        };
+        let rfc2217 = self.endpoint.protocol == TcpProtocol::Rfc2217;
+        for event in self.events.drain(..) {
+            match event {
+                // the server accepted the COM-PORT-OPTION offered in `open`
+                TelnetEvent::Negotiation(DO, COM_PORT_OPTION) if rfc2217 => {
+                    stream.write_all(&rfc2217::configure(&self.settings))?;
+                }
+                TelnetEvent::Negotiation(DONT | WONT, COM_PORT_OPTION) if rfc2217 => {
+                    print_warning(&format!(
+                        "{} does not support RFC 2217, the line settings were not applied",
+                        self.settings.port
+                    ));
+                }
+                TelnetEvent::Negotiation(_, COM_PORT_OPTION) if rfc2217 => {}
+                TelnetEvent::Negotiation(command, option) => {
+                    if option == OPTION_BINARY && matches!(command, DO | DONT) {
+                        self.binary.store(command == DO, Ordering::Relaxed);
+                    }
+                    if let Some(reply) = telnet::negotiation_reply(command, option) {
+                        stream.write_all(&reply)?;
+                    }
+                }
+                TelnetEvent::Subnegotiation(COM_PORT_OPTION, payload) if rfc2217 => {
+                    let reply = rfc2217::parse_reply(&payload);
+                    if let Some(ServerReply::ModemState(state)) = reply {
+                        self.modem_state = Some(state);
+                    }
+                    let mismatch = reply.and_then(|reply| rfc2217::reply_mismatch(&self.settings, &reply));
+                    if let Some(mismatch) = mismatch {
+                        print_warning(&format!("{}: {mismatch}", self.settings.port));
+                    }
+                }
+                TelnetEvent::Subnegotiation(..) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Source for TcpSource {
+    fn name(&self) -> String {
+        self.settings.port.clone()
+    }
+
+    fn summary(&self) -> String {
+        match self.endpoint.protocol {
+            TcpProtocol::Raw => "raw TCP".to_string(),
+            TcpProtocol::Telnet => "telnet".to_string(),
+            TcpProtocol::Rfc2217 => format!("{} via RFC 2217", self.settings.line_summary()),
+        }
+    }
+
+    fn open(&mut self) -> Result<()> {
+        let stream = self.endpoint.connect()?;
+        stream.set_read_timeout(Some(SERIAL_READ_TIMEOUT))?;
+        stream.set_write_timeout(Some(SERIAL_WRITE_TIMEOUT))?;
+        stream.set_nodelay(true)?;
+        if self.endpoint.protocol == TcpProtocol::Rfc2217 {
+            (&stream).write_all(&[IAC, WILL, COM_PORT_OPTION])?;
+        }
+        self.stream = Some(stream);
+        self.telnet = TelnetParser::new();
+        self.modem_state = None;
+        self.binary.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        if let Some(stream) = self.stream.take() {
+            let _ = stream.shutdown(std::net::Shutdown::Both);
+        }
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> ReadResult {
+        let Some(stream) = &mut self.stream else {
+            return ReadResult::Error;
+        };
+        let data_size = match stream.read(buffer) {
+            // the server closed the connection
+            Ok(0) => return ReadResult::Error,
+            Ok(data_size) => data_size,
+            Err(e) => return transport::read_result(Err(e)),
+        };
+        if !self.endpoint.protocol.uses_telnet() {
+            return ReadResult::Data(data_size);
+        }
+        let data_size = self.telnet.process(&mut buffer[..data_size], &mut self.events);
+        if self.answer_telnet_events().is_err() {
+            return ReadResult::Error;
+        }
+        match data_size {
+            0 => ReadResult::NoData,
+            data_size => ReadResult::Data(data_size),
+        }
+    }
+
+    fn writer(&self) -> Option<Box<dyn SourceWriter>> {
+        let stream = self.stream.as_ref()?.try_clone().ok()?;
+        Some(Box::new(TcpWriter {
+            stream,
+            telnet: self.endpoint.protocol.uses_telnet(),
+            binary: Arc::clone(&self.binary),
+            rfc2217: self.endpoint.protocol == TcpProtocol::Rfc2217,
+        }))
+    }
+
+    fn input_lines(&mut self) -> Option<InputLines> {
+        self.modem_state.map(InputLines::from_modem_state)
+    }
+}
+
+struct TcpWriter {
+    stream: TcpStream,
+    telnet: bool,
+    binary: Arc<AtomicBool>,
+    /// Whether the modem lines can be driven with RFC 2217 commands
+    rfc2217: bool,
+}
+
+impl TcpWriter {
+    fn send_command(&mut self, command: Vec<u8>) -> io::Result<()> {
+        if !self.rfc2217 {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "modem lines can only be driven through an rfc2217:// port",
+            ));
+        }
+        self.stream.write_all(&command)
+    }
+}
+
+impl Write for TcpWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.telnet {
+            let binary = self.binary.load(Ordering::Relaxed);
+            self.stream.write_all(&telnet::escape_data(buf, binary))?;
+            Ok(buf.len())
+        } else {
+            self.stream.write(buf)
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl SourceWriter for TcpWriter {
+    fn set_dtr(&mut self, level: bool) -> io::Result<()> {
+        self.send_command(rfc2217::set_dtr(level))
+    }
+
+    fn set_rts(&mut self, level: bool) -> io::Result<()> {
+        self.send_command(rfc2217::set_rts(level))
+    }
+
+    fn set_break(&mut self, on: bool) -> io::Result<()> {
+        self.send_command(rfc2217::set_break(on))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telnet::OPTION_ECHO;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_parse_endpoint() {
+        assert!(TcpEndpoint::parse("/dev/ttyUSB0").is_none());
+        let endpoint = TcpEndpoint::parse("telnet://lab-server:2001").unwrap().unwrap();
+        assert_eq!(endpoint.protocol, TcpProtocol::Telnet);
+        assert_eq!(endpoint.host, "lab-server");
+        assert_eq!(endpoint.port, 2001);
+        let endpoint = TcpEndpoint::parse("tcp://[::1]:4000").unwrap().unwrap();
+        assert_eq!(endpoint.host, "::1");
+        assert!(TcpEndpoint::parse("tcp://lab-server").unwrap().is_err());
+        assert!(TcpEndpoint::parse("tcp://:2001").unwrap().is_err());
+    }
+
+    #[test]
+    fn test_telnet_source_against_loopback_listener() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("telnet://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (mut client, _) = listener.accept().unwrap();
+            client.write_all(&[IAC, WILL, OPTION_ECHO]).unwrap();
+            client.write_all(b"U-Boot\r\n").unwrap();
+            let mut reply = [0; 3];
+            client.read_exact(&mut reply).unwrap();
+            reply
+        });
+        let settings = Settings::for_tests(&url);
+        let mut source = TcpSource::new(&settings, TcpEndpoint::parse(&url).unwrap().unwrap());
+        source.open().unwrap();
+        let mut received = Vec::new();
+        let mut buffer = [0; 64];
+        while received.len() < 8 {
+            match source.read(&mut buffer) {
+                ReadResult::Data(size) => received.extend_from_slice(&buffer[..size]),
+                ReadResult::NoData => {}
+                ReadResult::End | ReadResult::Error => panic!("connection lost"),
+            }
+        }
+        assert_eq!(received, b"U-Boot\r\n");
+        assert_eq!(server.join().unwrap(), [IAC, telnet::DO, OPTION_ECHO]);
+        // the listener is gone, so reading reports the drop
+        let result = loop {
+            match source.read(&mut buffer) {
+                ReadResult::NoData => continue,
+                result => break result,
+            }
+        };
+        assert!(matches!(result, ReadResult::Error));
+    }
+
+    #[test]
+    fn test_rfc2217_source_configures_server_port() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("rfc2217://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (mut client, _) = listener.accept().unwrap();
+            let mut offer = [0; 3];
+            client.read_exact(&mut offer).unwrap();
+            client.write_all(&[IAC, DO, COM_PORT_OPTION]).unwrap();
+            let mut baud_rate = [0; 10];
+            client.read_exact(&mut baud_rate).unwrap();
+            client.write_all(b"ready\n").unwrap();
+            (offer, baud_rate)
+        });
+        let settings = Settings::for_tests(&url);
+        let mut source = TcpSource::new(&settings, TcpEndpoint::parse(&url).unwrap().unwrap());
+        assert_eq!(source.summary(), "115200 8N1 via RFC 2217");
+        source.open().unwrap();
+        let mut received = Vec::new();
+        let mut buffer = [0; 64];
+        while received.len() < 6 {
+            match source.read(&mut buffer) {
+                ReadResult::Data(size) => received.extend_from_slice(&buffer[..size]),
+                ReadResult::NoData => {}
+                ReadResult::End | ReadResult::Error => panic!("connection lost"),
+            }
+        }
+        assert_eq!(received, b"ready\n");
+        let (offer, baud_rate) = server.join().unwrap();
+        assert_eq!(offer, [IAC, WILL, COM_PORT_OPTION]);
+        assert_eq!(baud_rate.to_vec(), rfc2217::set_baud_rate(115200));
+    }
+}
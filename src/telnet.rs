@@ -0,0 +1,190 @@
+pub const IAC: u8 = 255;
+pub const DONT: u8 = 254;
+pub const DO: u8 = 253;
+pub const WONT: u8 = 252;
+pub const WILL: u8 = 251;
+pub const SB: u8 = 250;
+pub const SE: u8 = 240;
+
+pub const OPTION_BINARY: u8 = 0;
+pub const OPTION_ECHO: u8 = 1;
+pub const OPTION_SUPPRESS_GO_AHEAD: u8 = 3;
+
+/// Something received from a telnet peer besides plain data
+#[derive(Debug, PartialEq, Eq)]
+pub enum TelnetEvent {
+    /// `WILL`, `WONT`, `DO` or `DONT` for an option
+    Negotiation(u8, u8),
+    /// The payload of `IAC SB <option> ... IAC SE`
+    Subnegotiation(u8, Vec<u8>),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ParserState {
+    Data,
+    Iac,
+    Negotiation(u8),
+    Subnegotiation,
+    SubnegotiationIac,
+    // NVT sends a bare CR as CR NUL, unless the peer transmits in binary
+    Cr,
+}
+
+/// Splits a telnet stream into data and events, keeping its state across reads
+pub struct TelnetParser {
+    state: ParserState,
+    subnegotiation: Vec<u8>,
+    /// Whether the peer said `WILL BINARY`, so a NUL after CR is data
+    peer_binary: bool,
+}
+
+impl TelnetParser {
+    pub fn new() -> Self {
+        TelnetParser {
+            state: ParserState::Data,
+            subnegotiation: Vec::new(),
+            peer_binary: false,
+        }
+    }
+
+    /// Removes telnet commands from `buffer` in place, returning the length of the data left
+    pub fn process(&mut self, buffer: &mut [u8], events: &mut Vec<TelnetEvent>) -> usize {
+        let mut data_len = 0;
+        for index in 0..buffer.len() {
+            let byte = buffer[index];
+            self.state = match (self.state, byte) {
+                (ParserState::Data | ParserState::Cr, IAC) => ParserState::Iac,
+                (ParserState::Cr, 0) if !self.peer_binary => ParserState::Data,
+                (ParserState::Data | ParserState::Cr, byte) => {
+                    buffer[data_len] = byte;
+                    data_len += 1;
+                    if byte == b'\r' { ParserState::Cr } else { ParserState::Data }
+                }
+                (ParserState::Iac, IAC) => {
+                    buffer[data_len] = IAC;
+                    data_len += 1;
+                    ParserState::Data
+                }
+                (ParserState::Iac, command @ (WILL | WONT | DO | DONT)) => ParserState::Negotiation(command),
+                (ParserState::Iac, SB) => {
+                    self.subnegotiation.clear();
+                    ParserState::Subnegotiation
+                }
+                // other commands (NOP, GA, ...) carry no data
+                (ParserState::Iac, _) => ParserState::Data,
+                (ParserState::Negotiation(command), option) => {
+                    if option == OPTION_BINARY && matches!(command, WILL | WONT) {
+                        self.peer_binary = command == WILL;
+                    }
+                    events.push(TelnetEvent::Negotiation(command, option));
+                    ParserState::Data
+                }
+                (ParserState::Subnegotiation, IAC) => ParserState::SubnegotiationIac,
+                (ParserState::Subnegotiation, byte) => {
+                    self.subnegotiation.push(byte);
+                    ParserState::Subnegotiation
+                }
+                (ParserState::SubnegotiationIac, SE) => {
+                    if let Some((&option, payload)) = self.subnegotiation.split_first() {
+                        events.push(TelnetEvent::Subnegotiation(option, payload.to_vec()));
+                    }
+                    ParserState::Data
+                }
+                (ParserState::SubnegotiationIac, byte) => {
+                    // `IAC IAC` inside a subnegotiation is an escaped 255
+                    self.subnegotiation.push(byte);
+                    ParserState::Subnegotiation
+                }
+            };
+        }
+        data_len
+    }
+}
+
+impl Default for TelnetParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reply refusing every option except the ones a plain data connection is happy with.
+/// The server may echo, but spewcap never echoes back what it receives.
+pub fn negotiation_reply(command: u8, option: u8) -> Option<[u8; 3]> {
+    let reply = match command {
+        WILL if matches!(option, OPTION_BINARY | OPTION_ECHO | OPTION_SUPPRESS_GO_AHEAD) => DO,
+        WILL => DONT,
+        DO if matches!(option, OPTION_BINARY | OPTION_SUPPRESS_GO_AHEAD) => WILL,
+        DO => WONT,
+        // acknowledgements of a refusal need no answer
+        _ => return None,
+    };
+    Some([IAC, reply, option])
+}
+
+/// Doubles every 255 byte so data is not mistaken for a command. Unless transmitting in binary,
+/// a CR that is not followed by LF is sent as CR NUL like NVT requires.
+pub fn escape_data(data: &[u8], binary: bool) -> Vec<u8> {
+    let mut escaped = Vec::with_capacity(data.len());
+    for (index, &byte) in data.iter().enumerate() {
+        escaped.push(byte);
+        match byte {
+            IAC => escaped.push(IAC),
+            b'\r' if !binary && data.get(index + 1) != Some(&b'\n') => escaped.push(0),
+            _ => {}
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parser_strips_commands_across_reads() {
+        let mut parser = TelnetParser::new();
+        let mut events = Vec::new();
+        let mut first = [b'o', b'k', IAC, WILL];
+        let len = parser.process(&mut first, &mut events);
+        assert_eq!(&first[..len], b"ok");
+        let mut second = [OPTION_ECHO, b'\r', 0, IAC, IAC, b'\n'];
+        let len = parser.process(&mut second, &mut events);
+        assert_eq!(&second[..len], &[b'\r', IAC, b'\n']);
+        assert_eq!(events, vec![TelnetEvent::Negotiation(WILL, OPTION_ECHO)]);
+    }
+
+    #[test]
+    fn test_parser_keeps_nul_after_cr_in_binary() {
+        let mut parser = TelnetParser::new();
+        let mut events = Vec::new();
+        let mut data = [IAC, WILL, OPTION_BINARY, b'\r', 0, 7];
+        let len = parser.process(&mut data, &mut events);
+        assert_eq!(&data[..len], &[b'\r', 0, 7]);
+        let mut data = [IAC, WONT, OPTION_BINARY, b'\r', 0, 7];
+        let len = parser.process(&mut data, &mut events);
+        assert_eq!(&data[..len], &[b'\r', 7]);
+    }
+
+    #[test]
+    fn test_parser_subnegotiation() {
+        let mut parser = TelnetParser::new();
+        let mut events = Vec::new();
+        let mut data = [b'a', IAC, SB, 44, 101, IAC, IAC, IAC, SE, b'b'];
+        let len = parser.process(&mut data, &mut events);
+        assert_eq!(&data[..len], b"ab");
+        assert_eq!(events, vec![TelnetEvent::Subnegotiation(44, vec![101, IAC])]);
+    }
+
+    #[test]
+    fn test_negotiation_reply_and_escape() {
+        assert_eq!(negotiation_reply(WILL, OPTION_ECHO), Some([IAC, DO, OPTION_ECHO]));
+        assert_eq!(negotiation_reply(DO, OPTION_ECHO), Some([IAC, WONT, OPTION_ECHO]));
+        assert_eq!(negotiation_reply(DO, OPTION_SUPPRESS_GO_AHEAD), Some([IAC, WILL, OPTION_SUPPRESS_GO_AHEAD]));
+        assert_eq!(negotiation_reply(DO, 24), Some([IAC, WONT, 24]));
+        assert_eq!(negotiation_reply(WONT, 24), None);
+        assert_eq!(escape_data(&[1, IAC, 2], false), vec![1, IAC, IAC, 2]);
+        assert_eq!(escape_data(b"ls\r", false), b"ls\r\0");
+        assert_eq!(escape_data(b"ls\r\n", false), b"ls\r\n");
+        assert_eq!(escape_data(b"ls\r", true), b"ls\r");
+    }
+}
@@ -0,0 +1,249 @@
+use chrono::format::{self, Item, Parsed, StrftimeItems};
+use chrono::{DateTime, Local, NaiveDateTime, NaiveTime, TimeDelta, Utc};
+use std::fmt::Write as FmtWrite;
+use std::ops::Add;
+use std::time::{Duration, Instant};
+
+use crate::constants::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimestampMode {
+    /// Time since the log (or connection) was started
+    Elapsed,
+    /// Local wall-clock time
+    Local,
+    /// UTC wall-clock time, ISO-8601 by default
+    Utc,
+    /// Time since the previous line
+    Delta,
+}
+
+/// A point in time as wall-clock time, for `Local` and `Utc` timestamps, and as a monotonic
+/// instant, so elapsed and delta timestamps don't jump when the system clock is stepped
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Moment {
+    pub wall: DateTime<Local>,
+    pub instant: Instant,
+}
+
+impl Moment {
+    pub fn now() -> Self {
+        Moment {
+            wall: Local::now(),
+            instant: Instant::now(),
+        }
+    }
+}
+
+impl Add<Duration> for Moment {
+    type Output = Moment;
+
+    fn add(self, duration: Duration) -> Moment {
+        Moment {
+            wall: self.wall + TimeDelta::from_std(duration).unwrap_or(TimeDelta::MAX),
+            instant: self.instant + duration,
+        }
+    }
+}
+
+pub struct Timestamper {
+    mode: TimestampMode,
+    format: Option<String>,
+    start: Moment,
+    previous: Option<Instant>,
+}
+
+impl Timestamper {
+    /// `start` is the base of elapsed timestamps, shared by everything captured in a session
+    pub fn new(mode: TimestampMode, format: Option<String>, start: Moment) -> Self {
+        Timestamper {
+            mode,
+            format,
+            start,
+            previous: None,
+        }
+    }
+
+    pub fn write_timestamp(&mut self, buffer: &mut String, time: Moment) -> std::fmt::Result {
+        match self.mode {
+            TimestampMode::Elapsed => {
+                self.write_duration(buffer, time.instant.saturating_duration_since(self.start.instant))
+            }
+            TimestampMode::Delta => {
+                let previous = self.previous.unwrap_or(self.start.instant);
+                self.previous = Some(time.instant);
+                self.write_duration(buffer, time.instant.saturating_duration_since(previous))
+            }
+            TimestampMode::Local => {
+                let format = self.format.as_deref().unwrap_or(DEFAULT_LOCAL_TIMESTAMP_FORMAT);
+                write!(buffer, "{}", time.wall.format(format))
+            }
+            TimestampMode::Utc => {
+                let format = self.format.as_deref().unwrap_or(DEFAULT_UTC_TIMESTAMP_FORMAT);
+                write!(buffer, "{}", time.wall.with_timezone(&Utc).format(format))
+            }
+        }
+    }
+
+    fn write_duration(&self, buffer: &mut String, duration: Duration) -> std::fmt::Result {
+        match &self.format {
+            // durations are formatted as a time of day, so they wrap after 24 hours
+            Some(format) => {
+                let time = NaiveTime::MIN + TimeDelta::from_std(duration).unwrap_or(TimeDelta::zero());
+                write!(buffer, "{}", time.format(format))
+            }
+            None => {
+                let total_millis = duration.as_millis();
+                let hours = total_millis / MILLIS_PER_HOUR;
+                let minutes = (total_millis % MILLIS_PER_HOUR) / MILLIS_PER_MINUTE;
+                let seconds = (total_millis % MILLIS_PER_MINUTE) / MILLIS_PER_SECOND;
+                let millis = total_millis % MILLIS_PER_SECOND;
+                write!(buffer, "{:02}:{:02}:{:02}:{:03}ms", hours, minutes, seconds, millis)
+            }
+        }
+    }
+}
+
+/// Reads back a timestamp written by `Timestamper`: the time since the start (or the previous
+/// line in delta mode), or the wall-clock time for `Local` and `Utc`
+pub fn parse_timestamp(mode: TimestampMode, format: Option<&str>, text: &str) -> Option<TimeDelta> {
+    match (mode, format) {
+        (TimestampMode::Elapsed | TimestampMode::Delta, None) => parse_default_duration(text),
+        (TimestampMode::Elapsed | TimestampMode::Delta, Some(format)) => parse_time_of_day(text, format),
+        (TimestampMode::Local, format) => {
+            parse_date_time(text, format.unwrap_or(DEFAULT_LOCAL_TIMESTAMP_FORMAT))
+        }
+        (TimestampMode::Utc, format) => parse_date_time(text, format.unwrap_or(DEFAULT_UTC_TIMESTAMP_FORMAT)),
+    }
+}
+
+/// Parses the "01:02:03:004ms" format used for durations without a custom format
+fn parse_default_duration(text: &str) -> Option<TimeDelta> {
+    let mut fields = text.strip_suffix("ms")?.split(':').map(|field| field.parse::<i64>().ok());
+    let (hours, minutes, seconds, millis) = (fields.next()??, fields.next()??, fields.next()??, fields.next()??);
+    if fields.next().is_some() {
+        return None;
+    }
+    Some(
+        TimeDelta::hours(hours)
+            + TimeDelta::minutes(minutes)
+            + TimeDelta::seconds(seconds)
+            + TimeDelta::milliseconds(millis),
+    )
+}
+
+fn parse_time_of_day(text: &str, format: &str) -> Option<TimeDelta> {
+    let mut parsed = Parsed::new();
+    format::parse(&mut parsed, text, StrftimeItems::new(format)).ok()?;
+    // fields the format leaves out (eg. the hours in "+%S%.3f") are zero
+    let hours = parsed.hour_div_12().unwrap_or(0) * 12 + parsed.hour_mod_12().unwrap_or(0);
+    Some(
+        TimeDelta::hours(hours.into())
+            + TimeDelta::minutes(parsed.minute().unwrap_or(0).into())
+            + TimeDelta::seconds(parsed.second().unwrap_or(0).into())
+            + TimeDelta::nanoseconds(parsed.nanosecond().unwrap_or(0).into()),
+    )
+}
+
+/// Formats with a date are measured from the epoch, time-only formats from midnight
+fn parse_date_time(text: &str, format: &str) -> Option<TimeDelta> {
+    match NaiveDateTime::parse_from_str(text, format) {
+        Ok(date_time) => Some(date_time - DateTime::UNIX_EPOCH.naive_utc()),
+        Err(_) => parse_time_of_day(text, format),
+    }
+}
+
+pub fn is_valid_format(format: &str) -> bool {
+    !StrftimeItems::new(format).any(|item| matches!(item, Item::Error))
+}
+
+/// Whether a timestamp can be written with the format in this mode. Durations are formatted as a
+/// time of day, so date fields like `%Y` fail for elapsed and delta timestamps.
+pub fn formats_in_mode(mode: TimestampMode, format: &str) -> bool {
+    let now = Moment::now();
+    let mut timestamper = Timestamper::new(mode, Some(format.to_string()), now);
+    timestamper.write_timestamp(&mut String::new(), now).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_elapsed_default_format() {
+        let mut timestamper = Timestamper::new(TimestampMode::Elapsed, None, Moment::now());
+        let time = timestamper.start + Duration::from_millis(3_723_004);
+        let mut buffer = String::new();
+        timestamper.write_timestamp(&mut buffer, time).unwrap();
+        assert_eq!(buffer, "01:02:03:004ms");
+    }
+
+    #[test]
+    fn test_delta_custom_format() {
+        let mut timestamper = Timestamper::new(TimestampMode::Delta, Some("+%S%.3f".to_string()), Moment::now());
+        let first = timestamper.start + Duration::from_millis(1500);
+        let second = first + Duration::from_millis(250);
+        let mut buffer = String::new();
+        timestamper.write_timestamp(&mut buffer, first).unwrap();
+        buffer.push(' ');
+        timestamper.write_timestamp(&mut buffer, second).unwrap();
+        assert_eq!(buffer, "+01.500 +00.250");
+    }
+
+    #[test]
+    fn test_utc_default_format() {
+        let mut timestamper = Timestamper::new(TimestampMode::Utc, None, Moment::now());
+        let wall = DateTime::parse_from_rfc3339("2024-05-01T12:30:45.123+02:00")
+            .unwrap()
+            .with_timezone(&Local);
+        let time = Moment { wall, instant: Instant::now() };
+        let mut buffer = String::new();
+        timestamper.write_timestamp(&mut buffer, time).unwrap();
+        assert_eq!(buffer, "2024-05-01T10:30:45.123Z");
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(
+            parse_timestamp(TimestampMode::Elapsed, None, "01:02:03:004ms"),
+            Some(TimeDelta::milliseconds(3_723_004))
+        );
+        assert_eq!(
+            parse_timestamp(TimestampMode::Delta, Some("+%S%.3f"), "+01.500"),
+            Some(TimeDelta::milliseconds(1500))
+        );
+        let first = parse_timestamp(TimestampMode::Utc, None, "2024-05-01T10:30:45.123Z").unwrap();
+        let second = parse_timestamp(TimestampMode::Utc, None, "2024-05-01T10:30:46.000Z").unwrap();
+        assert_eq!(second - first, TimeDelta::milliseconds(877));
+        assert!(parse_timestamp(TimestampMode::Local, None, "12:00:00.000").is_some());
+        assert!(parse_timestamp(TimestampMode::Elapsed, None, "U-Boot 2024.01").is_none());
+    }
+
+    #[test]
+    fn test_elapsed_ignores_wall_clock() {
+        let start = Moment::now();
+        let mut timestamper = Timestamper::new(TimestampMode::Elapsed, None, start);
+        // the clock was stepped back an hour, but the line still arrived 2 s after the start
+        let time = Moment {
+            wall: start.wall - TimeDelta::hours(1),
+            instant: start.instant + Duration::from_secs(2),
+        };
+        let mut buffer = String::new();
+        timestamper.write_timestamp(&mut buffer, time).unwrap();
+        assert_eq!(buffer, "00:00:02:000ms");
+    }
+
+    #[test]
+    fn test_is_valid_format() {
+        assert!(is_valid_format("%H:%M:%S%.3f"));
+        assert!(!is_valid_format("%Q"));
+    }
+
+    #[test]
+    fn test_formats_in_mode() {
+        assert!(formats_in_mode(TimestampMode::Elapsed, "%H:%M:%S%.3f"));
+        assert!(!formats_in_mode(TimestampMode::Elapsed, "%Y-%m-%d %H:%M:%S"));
+        assert!(!formats_in_mode(TimestampMode::Delta, "%d %H:%M"));
+        assert!(formats_in_mode(TimestampMode::Local, "%Y-%m-%d %H:%M:%S"));
+    }
+}
@@ -0,0 +1,83 @@
+use std::io::{self, Write};
+
+use crate::error::Result;
+use crate::modem::InputLines;
+use crate::replay::ReplaySource;
+use crate::serial::SerialSource;
+use crate::settings::Settings;
+use crate::state::State;
+use crate::tcp::{TcpEndpoint, TcpSource};
+
+pub enum ReadResult {
+    Data(usize),
+    NoData,
+    /// The source has nothing more to deliver (eg. a headless replay reached the end of its file)
+    End,
+    Error,
+}
+
+/// Where captured bytes come from (eg. a local serial port). `connect_loop` opens a source,
+/// reads it until it fails or a reconnect is requested, and opens it again.
+pub trait Source: Send {
+    /// Name used in status messages, eg. "/dev/ttyUSB0"
+    fn name(&self) -> String;
+
+    /// Connection details shown once connected, eg. "115200 8N1"
+    fn summary(&self) -> String;
+
+    fn open(&mut self) -> Result<()>;
+
+    fn close(&mut self);
+
+    /// Reads the next chunk; a source with nothing to deliver within its timeout returns `NoData`
+    fn read(&mut self, buffer: &mut [u8]) -> ReadResult;
+
+    /// A separate handle for transmitting from the command thread while reading continues
+    fn writer(&self) -> Option<Box<dyn SourceWriter>>;
+
+    /// The current state of CTS/DSR/DCD/RI, for sources that report them
+    fn input_lines(&mut self) -> Option<InputLines> {
+        None
+    }
+}
+
+/// The transmit half of an open source. Sources without modem lines keep the defaults, which
+/// fail with `Unsupported`.
+pub trait SourceWriter: Write + Send {
+    fn set_dtr(&mut self, _level: bool) -> io::Result<()> {
+        Err(no_modem_lines())
+    }
+
+    fn set_rts(&mut self, _level: bool) -> io::Result<()> {
+        Err(no_modem_lines())
+    }
+
+    fn set_break(&mut self, _on: bool) -> io::Result<()> {
+        Err(no_modem_lines())
+    }
+}
+
+fn no_modem_lines() -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, "the port has no modem control lines")
+}
+
+/// Builds the source described by the settings
+pub fn source_for(settings: &Settings, shared_state: &State) -> Box<dyn Source> {
+    if settings.replay.is_some() {
+        return Box::new(ReplaySource::new(settings, shared_state));
+    }
+    match TcpEndpoint::parse(&settings.port) {
+        Some(Ok(endpoint)) => Box::new(TcpSource::new(settings, endpoint)),
+        _ => Box::new(SerialSource::new(settings)),
+    }
+}
+
+/// Maps the result of a read with a timeout onto `ReadResult`
+pub fn read_result(result: io::Result<usize>) -> ReadResult {
+    match result {
+        Ok(0) => ReadResult::NoData,
+        Ok(data_size) => ReadResult::Data(data_size),
+        Err(e) if matches!(e.kind(), io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock) => ReadResult::NoData,
+        Err(_) => ReadResult::Error,
+    }
+}
@@ -0,0 +1,579 @@
+use colored::Colorize;
+use crossterm::event::{KeyCode, KeyModifiers};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{self, Clear, ClearType};
+use crossterm::{cursor, queue};
+use lazy_static::lazy_static;
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Instant;
+
+use crate::constants::*;
+use crate::editor::LineEditor;
+use crate::settings::Settings;
+use crate::state::State;
+use crate::utils::{self, get_log_state};
+use crate::error::Result;
+
+/// When set, captured output and status messages go to the scrollback instead of stdout
+static TUI_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    static ref SCREEN: Mutex<ScreenBuffer> = Mutex::new(ScreenBuffer::new(DEFAULT_SCROLLBACK_LINES));
+}
+
+pub fn is_active() -> bool {
+    TUI_ACTIVE.load(Ordering::Relaxed)
+}
+
+fn screen() -> MutexGuard<'static, ScreenBuffer> {
+    // a panic while holding the lock leaves the buffer usable, so recover it
+    SCREEN.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+pub fn enter(scrollback_lines: usize) -> Result<()> {
+    *screen() = ScreenBuffer::new(scrollback_lines);
+    utils::enter_alternate_screen()?;
+    TUI_ACTIVE.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+pub fn leave() -> Result<()> {
+    if TUI_ACTIVE.swap(false, Ordering::Relaxed) {
+        utils::leave_alternate_screen()?;
+    }
+    Ok(())
+}
+
+/// Returns to the normal screen while an interactive prompt runs; `true` if the TUI was active
+pub fn suspend() -> bool {
+    let active = is_active();
+    if active {
+        let _ = leave();
+    }
+    active
+}
+
+pub fn resume() {
+    if utils::enter_alternate_screen().is_ok() {
+        TUI_ACTIVE.store(true, Ordering::Relaxed);
+        screen().dirty = true;
+    }
+}
+
+pub fn push_message(message: &str) {
+    screen().push_message(message);
+}
+
+pub fn clear() {
+    screen().clear();
+}
+
+/// Captured-output sink that appends to the scrollback
+pub struct ScreenWriter;
+
+impl Write for ScreenWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        screen().push(&String::from_utf8_lossy(buf));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Scrollback history addressed by absolute line numbers, so a frozen view stays put while old
+/// lines are dropped from the front
+pub struct ScreenBuffer {
+    lines: VecDeque<String>,
+    first_index: usize,
+    capacity: usize,
+    // the last line has not been terminated yet
+    open_line: bool,
+    // a line ended on `\r`, so a following `\n` belongs to it
+    pending_cr: bool,
+    dirty: bool,
+}
+
+impl ScreenBuffer {
+    pub fn new(capacity: usize) -> Self {
+        ScreenBuffer {
+            lines: VecDeque::new(),
+            first_index: 0,
+            capacity: capacity.max(1),
+            open_line: false,
+            pending_cr: false,
+            dirty: true,
+        }
+    }
+
+    pub fn push(&mut self, text: &str) {
+        for c in text.chars() {
+            match c {
+                '\n' if self.pending_cr => self.pending_cr = false,
+                '\n' => self.end_line(),
+                '\r' => {
+                    self.end_line();
+                    self.pending_cr = true;
+                }
+                '\t' => self.push_str("    "),
+                // other control characters would move the cursor around the screen
+                c if c.is_control() && c != '\x1b' => self.pending_cr = false,
+                c => {
+                    let mut encoded = [0; 4];
+                    self.push_str(c.encode_utf8(&mut encoded));
+                }
+            }
+        }
+        self.dirty = true;
+    }
+
+    /// Adds a status message on its own line(s)
+    pub fn push_message(&mut self, message: &str) {
+        if self.open_line {
+            self.end_line();
+        }
+        self.pending_cr = false;
+        self.push(message);
+        if self.open_line || message.is_empty() {
+            self.end_line();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.first_index = self.end_index();
+        self.lines.clear();
+        self.open_line = false;
+        self.pending_cr = false;
+        self.dirty = true;
+    }
+
+    fn push_str(&mut self, text: &str) {
+        self.pending_cr = false;
+        if !self.open_line {
+            self.new_line();
+            self.open_line = true;
+        }
+        if let Some(line) = self.lines.back_mut() {
+            line.push_str(text);
+        }
+    }
+
+    fn end_line(&mut self) {
+        if !self.open_line {
+            self.new_line();
+        }
+        self.open_line = false;
+    }
+
+    fn new_line(&mut self) {
+        self.lines.push_back(String::new());
+        while self.lines.len() > self.capacity {
+            self.lines.pop_front();
+            self.first_index += 1;
+        }
+    }
+
+    pub fn first_index(&self) -> usize {
+        self.first_index
+    }
+
+    pub fn end_index(&self) -> usize {
+        self.first_index + self.lines.len()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&str> {
+        let offset = index.checked_sub(self.first_index)?;
+        self.lines.get(offset).map(String::as_str)
+    }
+
+    /// Finds the nearest line containing `query` (ignoring case and colors), starting at `from`
+    pub fn find(&self, query: &str, from: usize, backwards: bool) -> Option<usize> {
+        if query.is_empty() || self.lines.is_empty() {
+            return None;
+        }
+        let query = query.to_lowercase();
+        let matches = |index: &usize| {
+            self.get(*index).is_some_and(|line| {
+                utils::ansi_regex().replace_all(line, "").to_lowercase().contains(&query)
+            })
+        };
+        let from = from.clamp(self.first_index, self.end_index() - 1);
+        if backwards {
+            (self.first_index..=from).rev().find(matches)
+        } else {
+            (from..self.end_index()).find(matches)
+        }
+    }
+}
+
+struct Search {
+    query: String,
+    editing: bool,
+    current: Option<usize>,
+}
+
+/// Bytes per second, averaged over about a second
+struct ByteRate {
+    since: Instant,
+    bytes_at_start: u64,
+    rate: f64,
+}
+
+impl ByteRate {
+    fn update(&mut self, total_bytes: u64) -> bool {
+        let elapsed = self.since.elapsed();
+        if elapsed < TUI_RATE_INTERVAL {
+            return false;
+        }
+        self.rate = total_bytes.saturating_sub(self.bytes_at_start) as f64 / elapsed.as_secs_f64();
+        self.since = Instant::now();
+        self.bytes_at_start = total_bytes;
+        true
+    }
+
+    fn describe(&self) -> String {
+        match self.rate {
+            rate if rate >= 1_000_000.0 => format!("{:.1} MB/s", rate / 1_000_000.0),
+            rate if rate >= 1_000.0 => format!("{:.1} kB/s", rate / 1_000.0),
+            rate => format!("{:.0} B/s", rate),
+        }
+    }
+}
+
+/// View state of the full-screen mode, owned by the command thread
+pub struct Tui {
+    // absolute index of the top line while scrolled back; `None` follows new output
+    scroll_top: Option<usize>,
+    body_rows: usize,
+    size: (u16, u16),
+    search: Search,
+    rate: ByteRate,
+    redraw: bool,
+}
+
+impl Tui {
+    pub fn new() -> Self {
+        Tui {
+            scroll_top: None,
+            body_rows: 1,
+            size: (0, 0),
+            search: Search {
+                query: String::new(),
+                editing: false,
+                current: None,
+            },
+            rate: ByteRate {
+                since: Instant::now(),
+                bytes_at_start: 0,
+                rate: 0.0,
+            },
+            redraw: true,
+        }
+    }
+
+    pub fn request_redraw(&mut self) {
+        self.redraw = true;
+    }
+
+    /// Handles the scrolling and search keys; returns `false` for keys meant for the command handler
+    pub fn handle_key(&mut self, code: KeyCode, modifiers: KeyModifiers) -> bool {
+        if self.search.editing {
+            self.handle_search_key(code);
+            self.redraw = true;
+            return true;
+        }
+        let page = self.body_rows.max(1);
+        match code {
+            KeyCode::Up => self.scroll_up(1),
+            KeyCode::Down => self.scroll_down(1),
+            KeyCode::PageUp => self.scroll_up(page),
+            KeyCode::PageDown => self.scroll_down(page),
+            KeyCode::Home => self.scroll_top = Some(screen().first_index()),
+            KeyCode::End => self.scroll_top = None,
+            KeyCode::Char('/') => {
+                self.search.query.clear();
+                self.search.current = None;
+                self.search.editing = true;
+            }
+            KeyCode::F(3) if modifiers.contains(KeyModifiers::SHIFT) => self.find_match(true),
+            KeyCode::F(3) => self.find_match(false),
+            _ => return false,
+        }
+        self.redraw = true;
+        true
+    }
+
+    fn handle_search_key(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Esc => {
+                self.search.query.clear();
+                self.search.current = None;
+                self.search.editing = false;
+            }
+            KeyCode::Enter => self.search.editing = false,
+            KeyCode::Up => self.find_match(true),
+            KeyCode::Down => self.find_match(false),
+            KeyCode::Backspace => {
+                self.search.query.pop();
+                self.search_incremental();
+            }
+            KeyCode::Char(c) => {
+                self.search.query.push(c);
+                self.search_incremental();
+            }
+            _ => {}
+        }
+    }
+
+    /// Searches back from the bottom of the view (or the current match) as the query is typed
+    fn search_incremental(&mut self) {
+        let from = self.search.current.unwrap_or_else(|| self.view_bottom());
+        let found = screen().find(&self.search.query, from, true);
+        self.show_match(found);
+    }
+
+    /// Moves to the previous (older) or next (newer) match
+    fn find_match(&mut self, previous: bool) {
+        let view_bottom = self.view_bottom();
+        let found = {
+            let screen = screen();
+            let from = match self.search.current {
+                Some(current) if previous => current.checked_sub(1),
+                Some(current) => Some(current + 1).filter(|&next| next < screen.end_index()),
+                None => Some(view_bottom),
+            };
+            from.and_then(|from| screen.find(&self.search.query, from, previous))
+        };
+        if found.is_some() {
+            self.show_match(found);
+        }
+    }
+
+    fn show_match(&mut self, found: Option<usize>) {
+        self.search.current = found;
+        if let Some(index) = found {
+            let top = self.view_top();
+            if index < top || index >= top + self.body_rows {
+                self.scroll_top = Some(index.saturating_sub(self.body_rows / 2));
+            }
+        }
+    }
+
+    fn scroll_up(&mut self, rows: usize) {
+        let top = self.view_top();
+        self.scroll_top = Some(top.saturating_sub(rows).max(screen().first_index()));
+    }
+
+    fn scroll_down(&mut self, rows: usize) {
+        if let Some(top) = self.scroll_top {
+            let end = screen().end_index();
+            // reaching the bottom resumes following new output
+            self.scroll_top = (top + rows + self.body_rows < end).then_some(top + rows);
+        }
+    }
+
+    fn view_top(&self) -> usize {
+        let screen = screen();
+        let latest_top = screen.end_index().saturating_sub(self.body_rows).max(screen.first_index());
+        match self.scroll_top {
+            Some(top) => top.clamp(screen.first_index(), latest_top),
+            None => latest_top,
+        }
+    }
+
+    fn view_bottom(&self) -> usize {
+        (self.view_top() + self.body_rows).saturating_sub(1)
+    }
+
+    /// Redraws the screen if anything changed since the last frame
+    pub fn draw(&mut self, settings: &Settings, shared_state: &State, editor: &LineEditor) -> io::Result<()> {
+        if !is_active() {
+            return Ok(());
+        }
+        let size = terminal::size()?;
+        let total_bytes = shared_state.bytes_received.load(Ordering::Relaxed);
+        let rate_changed = self.rate.update(total_bytes);
+        let screen_changed = std::mem::take(&mut screen().dirty);
+        if !(self.redraw || rate_changed || screen_changed || size != self.size) {
+            return Ok(());
+        }
+        self.redraw = false;
+        self.size = size;
+        let (width, height) = (size.0 as usize, size.1 as usize);
+        let input_mode = shared_state.input_mode.load(Ordering::Relaxed);
+        let prompt_rows = usize::from(self.search.editing || input_mode);
+        self.body_rows = height.saturating_sub(1 + prompt_rows).max(1);
+
+        let mut stdout = io::stdout();
+        let top = self.view_top();
+        {
+            let screen = screen();
+            for row in 0..self.body_rows {
+                let index = top + row;
+                queue!(stdout, cursor::MoveTo(0, row as u16), Clear(ClearType::CurrentLine))?;
+                if let Some(line) = screen.get(index) {
+                    let line = clip_visible(line, width);
+                    if self.search.current == Some(index) {
+                        queue!(stdout, SetAttribute(Attribute::Reverse), Print(line), SetAttribute(Attribute::Reset))?;
+                    } else {
+                        queue!(stdout, Print(line))?;
+                    }
+                }
+            }
+        }
+        let status = self.status_line(settings, shared_state, top);
+        queue!(
+            stdout,
+            cursor::MoveTo(0, (height.saturating_sub(1)) as u16),
+            Clear(ClearType::CurrentLine),
+            SetAttribute(Attribute::Reverse),
+            Print(format!("{:<width$}", clip_visible(&status, width), width = width)),
+            SetAttribute(Attribute::Reset)
+        )?;
+        if prompt_rows > 0 {
+            let (prompt, text, cursor) = if self.search.editing {
+                ("/", self.search.query.clone(), self.search.query.chars().count())
+            } else {
+                (INPUT_PROMPT, editor.text(), editor.cursor())
+            };
+            let prompt_len = prompt.chars().count();
+            queue!(
+                stdout,
+                cursor::MoveTo(0, self.body_rows as u16),
+                Clear(ClearType::CurrentLine),
+                Print(prompt.cyan()),
+                Print(text),
+                cursor::MoveTo((prompt_len + cursor) as u16, self.body_rows as u16),
+                cursor::Show
+            )?;
+        } else {
+            queue!(stdout, cursor::Hide)?;
+        }
+        stdout.flush()
+    }
+
+    fn status_line(&self, settings: &Settings, shared_state: &State, top: usize) -> String {
+        let connection = if shared_state.connected.load(Ordering::Relaxed) {
+            "connected"
+        } else {
+            "disconnected"
+        };
+        let log = match get_log_state(shared_state) {
+            Ok(log_state) => match &log_state.active_log {
+                Some(log) if log.is_enabled() => format!("log: {}", log.get_filename()),
+                Some(log) => format!("log: {} (paused)", log.get_filename()),
+                None => "no log".to_string(),
+            },
+            Err(_) => "log: ?".to_string(),
+        };
+        let port = if settings.is_multi_port() {
+            let labels: Vec<&str> = settings.ports.iter().map(|spec| spec.label.as_str()).collect();
+            format!("{} ports ({})", labels.len(), labels.join(", "))
+        } else {
+            format!("{} {}", settings.port, settings.line_summary())
+        };
+        let mut status = format!(
+            " {} | {} | {} | {}",
+            port,
+            connection,
+            log,
+            self.rate.describe()
+        );
+        if shared_state.capture_paused.load(Ordering::Relaxed) {
+            status.push_str(" | capture paused");
+        }
+        if self.scroll_top.is_some() {
+            let hidden = screen().end_index().saturating_sub(top + self.body_rows);
+            status.push_str(&format!(" | scrolled (+{hidden} lines, End to follow)"));
+        }
+        if !self.search.query.is_empty() {
+            let state = if self.search.current.is_some() { "" } else { " (no match)" };
+            status.push_str(&format!(" | search: {}{}", self.search.query, state));
+        }
+        status
+    }
+}
+
+impl Default for Tui {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Cuts a line to `width` visible characters, keeping ANSI color codes intact
+fn clip_visible(line: &str, width: usize) -> String {
+    let mut clipped = String::with_capacity(line.len().min(width * 2));
+    let mut visible = 0;
+    let mut has_escapes = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            has_escapes = true;
+            clipped.push(c);
+            for c in chars.by_ref() {
+                clipped.push(c);
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+            continue;
+        }
+        if visible == width {
+            break;
+        }
+        clipped.push(c);
+        visible += 1;
+    }
+    if has_escapes {
+        clipped.push_str("\x1b[0m");
+    }
+    clipped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_screen_buffer_lines_and_capacity() {
+        let mut buffer = ScreenBuffer::new(3);
+        buffer.push("one\r\ntw");
+        buffer.push("o\nthree\rfour\n");
+        assert_eq!(buffer.first_index(), 1);
+        assert_eq!(buffer.end_index(), 4);
+        assert_eq!(buffer.get(1), Some("two"));
+        assert_eq!(buffer.get(3), Some("four"));
+        assert_eq!(buffer.get(0), None);
+    }
+
+    #[test]
+    fn test_screen_buffer_messages_start_new_line() {
+        let mut buffer = ScreenBuffer::new(10);
+        buffer.push("login: ");
+        buffer.push_message("Log started");
+        buffer.push("root\n");
+        assert_eq!(buffer.get(0), Some("login: "));
+        assert_eq!(buffer.get(1), Some("Log started"));
+        assert_eq!(buffer.get(2), Some("root"));
+    }
+
+    #[test]
+    fn test_screen_buffer_find() {
+        let mut buffer = ScreenBuffer::new(10);
+        buffer.push("boot\nERROR one\nok\n\x1b[31mError\x1b[0m two\n");
+        assert_eq!(buffer.find("error", 3, true), Some(3));
+        assert_eq!(buffer.find("error", 2, true), Some(1));
+        assert_eq!(buffer.find("error", 2, false), Some(3));
+        assert_eq!(buffer.find("panic", 3, true), None);
+    }
+
+    #[test]
+    fn test_clip_visible_keeps_colors() {
+        assert_eq!(clip_visible("abcdef", 3), "abc");
+        assert_eq!(clip_visible("\x1b[31mabcdef", 2), "\x1b[31mab\x1b[0m");
+    }
+}
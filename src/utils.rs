@@ -1,27 +1,33 @@
 use colored::Colorize;
 use crossterm::terminal;
 use crossterm::execute;
-// use crossterm::terminal::{EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::terminal::{EnterAlternateScreen, LeaveAlternateScreen};
 use lazy_static::lazy_static;
 use regex::Regex;
 use rfd::FileDialog;
 use serialport5::{available_ports, SerialPortType};
 use std::fmt::Display;
 use std::ops::Deref;
-use std::path::PathBuf;
-use std::sync::atomic::Ordering;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, MutexGuard};
 use std::thread::JoinHandle;
 use std::time::Duration;
 
 use crate::constants::SIGNAL_MONITOR_SLEEP;
-use crate::log::LogFile;
+use crate::limits::StopReason;
+use crate::log::{port_log_path, LogFile};
 use crate::settings::Settings;
+use crate::timestamp::Moment;
+use crate::tui;
 use crate::state::{LogState, State};
 use crate::error::{Result, SpewcapError};
 
 const ANSI_REGEX: &str = r"\x1b\[[0-9;]*[mK]";
 
+/// When set, status messages go to stderr so stdout only carries captured data
+static MESSAGES_TO_STDERR: AtomicBool = AtomicBool::new(false);
+
 lazy_static! {
     static ref ANSI_REGEX_COMPILED: Regex = Regex::new(ANSI_REGEX)
         .expect("Invalid ANSI regex pattern - this is a compile-time constant");
@@ -104,21 +110,25 @@ pub fn sleep_ms(num_ms: u64) {
     std::thread::sleep(Duration::from_millis(num_ms));
 }
 
-// pub fn enter_alternate_screen() -> Result<()> {
-//     execute!(std::io::stdout(), EnterAlternateScreen)
-//         .map_err(|e| SpewcapError::Terminal(format!("Failed to enter alternate screen: {e}")))?;
-//     execute!(std::io::stdout(), crossterm::cursor::MoveTo(0, 0))
-//         .map_err(|e| SpewcapError::Terminal(format!("Failed to move cursor to top: {e}")))?;
-//     Ok(())
-// }
+pub fn enter_alternate_screen() -> Result<()> {
+    execute!(std::io::stdout(), EnterAlternateScreen)
+        .map_err(|e| SpewcapError::Terminal(format!("Failed to enter alternate screen: {e}")))?;
+    execute!(std::io::stdout(), crossterm::cursor::MoveTo(0, 0))
+        .map_err(|e| SpewcapError::Terminal(format!("Failed to move cursor to top: {e}")))?;
+    Ok(())
+}
 
-// pub fn leave_alternate_screen() -> Result<()> {
-//     execute!(std::io::stdout(), LeaveAlternateScreen)
-//         .map_err(|e| SpewcapError::Terminal(format!("Failed to leave alternate screen: {e}")))?;
-//     Ok(())
-// }
+pub fn leave_alternate_screen() -> Result<()> {
+    execute!(std::io::stdout(), crossterm::cursor::Show, LeaveAlternateScreen)
+        .map_err(|e| SpewcapError::Terminal(format!("Failed to leave alternate screen: {e}")))?;
+    Ok(())
+}
 
 pub fn clear_console() {
+    if tui::is_active() {
+        tui::clear();
+        return;
+    }
     if let Err(e) = execute!(std::io::stdout(), terminal::Clear(terminal::ClearType::All)) {
         print_error(&format!("Failed to clear console: {e}"));
     }
@@ -148,6 +158,28 @@ pub fn reset_ansi() {
     print!("\x1b[0m")
 }
 
+/// Forces colored output on or off until dropped. The override is global, so tests that
+/// check colors hold a shared lock while it is set.
+#[cfg(test)]
+pub struct ColorOverride {
+    _lock: std::sync::MutexGuard<'static, ()>,
+}
+
+#[cfg(test)]
+pub fn override_colors(enabled: bool) -> ColorOverride {
+    static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    let guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    colored::control::set_override(enabled);
+    ColorOverride { _lock: guard }
+}
+
+#[cfg(test)]
+impl Drop for ColorOverride {
+    fn drop(&mut self) {
+        colored::control::unset_override();
+    }
+}
+
 pub fn start_thread<F>(settings: Settings, state: &State, task: F) -> JoinHandle<Result<()>>
 where
     F: Fn(Settings, State) -> Result<()> + Send + 'static,
@@ -182,18 +214,38 @@ where
 //     }
 // }
 
+pub fn send_messages_to_stderr() {
+    MESSAGES_TO_STDERR.store(true, Ordering::Relaxed);
+}
+
+pub fn print_line<T: Display>(text: T) {
+    if tui::is_active() {
+        tui::push_message(&text.to_string());
+    } else if MESSAGES_TO_STDERR.load(Ordering::Relaxed) {
+        eprintln!("{}", text);
+    } else {
+        println!("{}", text);
+    }
+}
+
 pub fn print_separator() {
-    reset_ansi();
+    // the scrollback keeps status messages on their own lines without framing
+    if tui::is_active() {
+        return;
+    }
+    if !MESSAGES_TO_STDERR.load(Ordering::Relaxed) {
+        reset_ansi();
+    }
     if let Some((width, _)) = term_size::dimensions() {
         let separator = "-".repeat(width);
-        println!("{}", separator);
+        print_line(separator);
     } else {
-        println!("----------------------------------------------");
+        print_line("----------------------------------------------");
     }
 }
 pub fn print_message<T: ToString + Deref<Target = str> + Display>(message: T) {
     print_separator();
-    println!("{}", message);
+    print_line(message);
     print_separator();
 }
 pub fn print_success(message: &str) {
@@ -236,6 +288,25 @@ pub fn request_quit(settings: &Settings, shared_state: &State) {
     }
     shared_state.quit_requested.store(true, Ordering::Relaxed);
 }
+/// Runs an interactive prompt with capture output suspended and raw mode disabled
+pub fn run_prompt<T, F>(shared_state: &State, prompt: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T>,
+{
+    shared_state.port_suspended.store(true, Ordering::Relaxed);
+    let tui_suspended = tui::suspend();
+    let _ = terminal::disable_raw_mode();
+    let result = prompt();
+    if let Err(e) = terminal::enable_raw_mode() {
+        print_warning(&format!("Could not re-enable raw mode: {e}"));
+    }
+    if tui_suspended {
+        tui::resume();
+    }
+    shared_state.port_suspended.store(false, Ordering::Relaxed);
+    result
+}
+
 pub fn quit_requested(state: &State) -> bool {
     state.quit_requested.load(Ordering::Relaxed)
 }
@@ -247,21 +318,62 @@ pub fn request_quit_with_state(shared_state: &State) {
     }
 }
 
+/// Ends the capture because a configured limit was reached, keeping the active log
+pub fn stop_capture(shared_state: &State, reason: StopReason) {
+    print_message(format!("Stopping capture: {}", reason.to_string().yellow()));
+    if let Ok(mut stop_reason) = shared_state.stop_reason.lock() {
+        stop_reason.get_or_insert(reason);
+    }
+    match get_log_state(shared_state) {
+        Ok(mut log_state) => log_state.logs_mut().for_each(LogFile::disable_cleanup),
+        Err(e) => print_error(&format!("Failed to keep log after stopping capture: {e}")),
+    }
+    request_quit_with_state(shared_state);
+}
+
+pub fn stop_reason(shared_state: &State) -> Option<StopReason> {
+    shared_state.stop_reason.lock().ok().and_then(|reason| *reason)
+}
+
 pub fn start_new_log(settings: &Settings, shared_state: &State) -> Result<()> {
     let mut log_state = shared_state.log_state.lock()
         .map_err(|e| SpewcapError::Log(format!("Failed to acquire lock: {e}")))?;
-    match LogFile::new(settings.timestamps) {
-        Ok(log) => {
-            let filename = log.get_filename().to_string();
-            log_state.active_log = Some(log);
-            print_success(&format!("Started new log file: {}", filename));
-            Ok(())
+    let session = log_state.session_count + 1;
+    let file_path = settings
+        .log_file
+        .clone()
+        .unwrap_or_else(|| crate::log::log_file_path(settings, session));
+    // every log of the session counts elapsed time from the same instant
+    let start = Moment::now();
+    let open_log = |path: PathBuf| match LogFile::new(path.clone(), settings, start) {
+        Ok(mut log) => {
+            if settings.log_file.is_some() || settings.headless {
+                log.disable_cleanup();
+            }
+            Ok(log)
         }
         Err(e) => {
             print_error("Failed to create log file");
-            Err(SpewcapError::Log(format!("Failed to create log file: {e}")))
+            Err(SpewcapError::Log(format!("Failed to create log file {}: {e}", path.display())))
         }
+    };
+    // the log may have been given a suffix, so the per-port logs follow its actual name
+    let merged_log = open_log(file_path)?;
+    let merged_path = merged_log.get_file_path().to_path_buf();
+    let mut port_logs = Vec::new();
+    if settings.log_per_port && settings.is_multi_port() {
+        for spec in &settings.ports {
+            port_logs.push(open_log(port_log_path(&merged_path, &spec.label))?);
+        }
+    }
+    for log in std::iter::once(&merged_log).chain(&port_logs) {
+        print_success(&format!("Started new log file: {}", log.get_file_path().display()));
     }
+    // replacing the logs removes their unkept working files, which the new logs never reuse
+    log_state.active_log = Some(merged_log);
+    log_state.port_logs = port_logs;
+    log_state.session_count = session;
+    Ok(())
 }
 pub fn run_file_dialog(filename: &str, directory: &Option<PathBuf>) -> Option<PathBuf> {
     let dialog = FileDialog::new();
@@ -286,35 +398,54 @@ pub fn save_active_log(settings: &Settings, shared_state: &State) {
         }
     };
     
+    let log_state = &mut *log_state;
     match log_state.active_log {
         Some(ref mut log) => {
-            save_log_with_dialog(log, settings);
+            if let Some(log_path) = save_log_with_dialog(log, settings) {
+                save_port_logs(&mut log_state.port_logs, &log_path, settings);
+            }
         }
         None => print_warning("No log started! Press `L` to start one"),
     }
 }
 
-fn save_log_with_dialog(log: &mut LogFile, settings: &Settings) {
+/// Saves the per-port logs next to the merged log, following its new name
+fn save_port_logs(port_logs: &mut [LogFile], merged_path: &Path, settings: &Settings) {
+    for (log, spec) in port_logs.iter_mut().zip(&settings.ports) {
+        let log_path = port_log_path(merged_path, &spec.label);
+        match log.save_as_and_keep(&log_path) {
+            Ok(()) => print_success(&format!("Saved {} log to {}", spec.label, log_path.display())),
+            Err(e) => print_error(&format!("Failed to save {} log: {e}", spec.label)),
+        }
+    }
+}
+
+/// Returns where the log was saved
+fn save_log_with_dialog(log: &mut LogFile, settings: &Settings) -> Option<PathBuf> {
     let _ = log.force_flush();
     
     if !log.has_unsaved_changes() {
         print_warning("No unsaved changes to save!");
-        return;
+        return None;
     }
     
     let log_path = match run_file_dialog(log.get_filename(), &settings.log_folder) {
         Some(path) => path,
         None => {
             print_warning("Save operation was canceled!");
-            return;
+            return None;
         }
     };
     
     match log.save_as_and_keep(&log_path) {
         Ok(()) => {
             print_success(&format!("Saved log to {}", log_path.display()));
+            Some(log_path)
+        }
+        Err(e) => {
+            print_error(&format!("Failed to save log: {e}"));
+            None
         }
-        Err(e) => print_error(&format!("Failed to save log: {e}")),
     }
 }
 
@@ -355,13 +486,18 @@ pub fn list_ports() -> Result<()> {
 
 pub fn cleanup_logs(shared_state: &State) {
     if let Ok(mut log_state) = get_log_state(shared_state) {
-        if let Some(ref mut log) = log_state.active_log {
+        for log in log_state.logs_mut() {
             if let Err(e) = log.ensure_flushed() {
                 eprintln!("Warning: Failed to flush log during cleanup: {e}");
             }
             
-            if log.has_unsaved_changes() {
-                print_warning("Active log has unsaved changes. It will be cleaned up unless saved.");
+            if log.is_kept() {
+                print_message(format!("Log kept at {}", log.get_file_path().display()));
+            } else if log.has_unsaved_changes() {
+                print_warning(&format!(
+                    "{} has unsaved changes. It will be cleaned up unless saved.",
+                    log.get_filename()
+                ));
             }
         }
     }
@@ -370,7 +506,7 @@ pub fn cleanup_logs(shared_state: &State) {
 pub fn emergency_cleanup_logs(shared_state: &State) {
     match shared_state.log_state.try_lock() {
         Ok(mut log_state) => {
-            if let Some(ref mut log) = log_state.active_log {
+            for log in log_state.logs_mut() {
                 let _ = log.ensure_flushed();
                 let _ = log.cleanup_temp_file();
             }
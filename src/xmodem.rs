@@ -0,0 +1,662 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use crate::constants::*;
+use crate::error::{Result, SpewcapError};
+use crate::serial;
+use crate::state::State;
+use crate::transport::{ReadResult, Source};
+use crate::utils::{self, print_error, print_message, print_success, print_warning};
+
+const SOH: u8 = 0x01;
+const STX: u8 = 0x02;
+const EOT: u8 = 0x04;
+const ACK: u8 = 0x06;
+const NAK: u8 = 0x15;
+const CAN: u8 = 0x18;
+/// Pads the last block of a file
+const SUB: u8 = 0x1a;
+/// Sent by a receiver instead of NAK to ask for CRC-16 rather than a checksum
+const CRC_REQUEST: u8 = b'C';
+
+const SHORT_BLOCK_SIZE: usize = 128;
+const LONG_BLOCK_SIZE: usize = 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Protocol {
+    /// 128-byte blocks, with CRC-16 (or a checksum for receivers that ask for one)
+    XmodemCrc,
+    /// 1024-byte blocks
+    Xmodem1k,
+    /// 1024-byte blocks after a header block carrying the file's name and size
+    Ymodem,
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Protocol::XmodemCrc => write!(f, "XMODEM-CRC"),
+            Protocol::Xmodem1k => write!(f, "XMODEM-1K"),
+            Protocol::Ymodem => write!(f, "YMODEM"),
+        }
+    }
+}
+
+/// A file waiting for the capture thread to hand it the port
+pub struct TransferRequest {
+    pub path: PathBuf,
+    pub data: Vec<u8>,
+    pub protocol: Protocol,
+}
+
+/// The byte stream a transfer runs over
+trait Channel {
+    /// Waits up to `timeout` for the next byte
+    fn read_byte(&mut self, timeout: Duration) -> Result<Option<u8>>;
+
+    fn write_all(&mut self, data: &[u8]) -> Result<()>;
+
+    /// Whether the user asked to stop the transfer
+    fn cancelled(&self) -> bool;
+}
+
+/// Queues a transfer for the capture thread of the main port, which runs it in place of capture
+pub fn request(shared_state: &State, request: TransferRequest) -> Result<()> {
+    let mut pending = shared_state
+        .pending_transfer
+        .lock()
+        .map_err(|e| SpewcapError::Transfer(format!("Failed to acquire lock on pending transfer: {e}")))?;
+    print_message(format!(
+        "Waiting for the receiver to start {}, `Esc` cancels",
+        request.protocol
+    ));
+    *pending = Some(request);
+    Ok(())
+}
+
+pub fn is_pending(shared_state: &State) -> bool {
+    // paced sends set `transfer_active` too, but never queue a transfer
+    shared_state.transfer_active.load(Ordering::Relaxed)
+        && shared_state.pending_transfer.lock().is_ok_and(|pending| pending.is_some())
+}
+
+/// Drops a transfer the capture thread has not picked up yet, returning whether there was one
+pub fn cancel_pending(shared_state: &State) -> bool {
+    shared_state
+        .pending_transfer
+        .lock()
+        .map(|mut pending| pending.take().is_some())
+        .unwrap_or(false)
+}
+
+/// Runs a queued transfer over the source, if there is one. Called from `serial::read_loop`,
+/// which resumes capture once this returns.
+pub fn run_pending(source: &mut dyn Source, shared_state: &State) {
+    let request = match shared_state.pending_transfer.lock() {
+        Ok(mut pending) => pending.take(),
+        Err(_) => None,
+    };
+    let Some(request) = request else {
+        return;
+    };
+    let name = request
+        .path
+        .file_name()
+        .map_or_else(|| request.path.display().to_string(), |name| name.to_string_lossy().into_owned());
+    let mut channel = PortChannel::new(source, shared_state);
+    let mut sender = Sender::new(&mut channel, request.protocol);
+    match sender.send(&name, &request.data) {
+        Ok(()) => print_success(&format!(
+            "Sent {name} over {} ({} blocks, {} retries)",
+            request.protocol, sender.blocks_sent, sender.retries
+        )),
+        Err(_) if shared_state.transfer_cancel.load(Ordering::Relaxed) => {
+            print_warning(&format!("Transfer of {name} was cancelled"))
+        }
+        Err(e) => print_error(&format!("Transfer of {name} failed: {e}")),
+    }
+    shared_state.transfer_active.store(false, Ordering::Relaxed);
+}
+
+/// The sending side of a transfer
+struct Sender<'a, C: Channel> {
+    channel: &'a mut C,
+    protocol: Protocol,
+    crc: bool,
+    blocks_sent: usize,
+    blocks_total: usize,
+    retries: usize,
+    last_report: Instant,
+}
+
+impl<'a, C: Channel> Sender<'a, C> {
+    fn new(channel: &'a mut C, protocol: Protocol) -> Self {
+        Sender {
+            channel,
+            protocol,
+            crc: true,
+            blocks_sent: 0,
+            blocks_total: 0,
+            retries: 0,
+            last_report: Instant::now(),
+        }
+    }
+
+    fn send(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        let result = self.send_file(name, data);
+        if result.is_err() {
+            // tell the receiver to give up rather than wait for blocks that never come
+            let _ = self.channel.write_all(&[CAN, CAN, CAN]);
+        }
+        result
+    }
+
+    fn send_file(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        let blocks = self.blocks(data);
+        self.blocks_total = blocks.len();
+        self.wait_for_start()?;
+        if self.protocol == Protocol::Ymodem {
+            self.send_block(0, &header_block(name, data.len()))?;
+            // the receiver asks for the data blocks once it has opened the file
+            self.wait_for_start()?;
+        }
+        for (index, block) in blocks.iter().enumerate() {
+            // block numbers start at 1 and wrap around
+            self.send_block((index + 1) as u8, block)?;
+            self.blocks_sent += 1;
+            self.report_progress();
+        }
+        self.send_end_of_file()?;
+        if self.protocol == Protocol::Ymodem {
+            // an empty header block ends the batch
+            self.wait_for_start()?;
+            self.send_block(0, &[0; SHORT_BLOCK_SIZE])?;
+        }
+        Ok(())
+    }
+
+    /// The file split into padded blocks, ending on a short block when the rest fits in one
+    fn blocks(&self, data: &[u8]) -> Vec<Vec<u8>> {
+        let block_size = match self.protocol {
+            Protocol::XmodemCrc => SHORT_BLOCK_SIZE,
+            Protocol::Xmodem1k | Protocol::Ymodem => LONG_BLOCK_SIZE,
+        };
+        let mut blocks = Vec::new();
+        let mut rest = data;
+        while !rest.is_empty() {
+            let size = if rest.len() <= SHORT_BLOCK_SIZE { SHORT_BLOCK_SIZE } else { block_size };
+            let (block, remaining) = rest.split_at(rest.len().min(size));
+            let mut block = block.to_vec();
+            block.resize(size, SUB);
+            blocks.push(block);
+            rest = remaining;
+        }
+        blocks
+    }
+
+    /// Waits for the receiver to ask for the next block, picking CRC-16 or a checksum for XMODEM
+    fn wait_for_start(&mut self) -> Result<()> {
+        let start = Instant::now();
+        while start.elapsed() < TRANSFER_START_TIMEOUT {
+            self.check_cancelled()?;
+            match self.channel.read_byte(TRANSFER_POLL_INTERVAL)? {
+                Some(CRC_REQUEST) => {
+                    self.crc = true;
+                    return self.purge();
+                }
+                Some(NAK) if self.protocol != Protocol::Ymodem => {
+                    self.crc = false;
+                    return self.purge();
+                }
+                Some(CAN) => self.check_receiver_cancel()?,
+                // bootloaders print a banner before they start asking
+                _ => {}
+            }
+        }
+        Err(SpewcapError::Transfer(format!(
+            "the receiver did not start within {} seconds",
+            TRANSFER_START_TIMEOUT.as_secs()
+        )))
+    }
+
+    fn send_block(&mut self, number: u8, block: &[u8]) -> Result<()> {
+        let packet = packet(number, block, self.crc);
+        for attempt in 0..=TRANSFER_MAX_RETRIES {
+            if attempt > 0 {
+                self.retries += 1;
+                self.purge()?;
+            }
+            self.channel.write_all(&packet)?;
+            if self.wait_for_ack()? {
+                return Ok(());
+            }
+        }
+        Err(SpewcapError::Transfer(format!(
+            "block {number} was rejected {} times",
+            TRANSFER_MAX_RETRIES + 1
+        )))
+    }
+
+    fn send_end_of_file(&mut self) -> Result<()> {
+        // YMODEM receivers NAK the first EOT to make sure it was not line noise
+        for attempt in 0..=TRANSFER_MAX_RETRIES {
+            if attempt > 0 {
+                if self.protocol != Protocol::Ymodem {
+                    self.retries += 1;
+                }
+                self.purge()?;
+            }
+            self.channel.write_all(&[EOT])?;
+            if self.wait_for_ack()? {
+                return Ok(());
+            }
+        }
+        Err(SpewcapError::Transfer("the end of the file was not acknowledged".to_string()))
+    }
+
+    /// Reads the receiver's answer to a packet: true for ACK, false for NAK or no answer
+    fn wait_for_ack(&mut self) -> Result<bool> {
+        let start = Instant::now();
+        while start.elapsed() < TRANSFER_REPLY_TIMEOUT {
+            self.check_cancelled()?;
+            match self.channel.read_byte(TRANSFER_POLL_INTERVAL)? {
+                Some(ACK) => return Ok(true),
+                // a receiver still asking to start missed the packet too
+                Some(NAK | CRC_REQUEST) => return Ok(false),
+                Some(CAN) => self.check_receiver_cancel()?,
+                _ => {}
+            }
+        }
+        Ok(false)
+    }
+
+    /// Drops what is already waiting, so start requests a receiver repeated while it waited
+    /// (or a NAK crossing a retry) are not taken as the answer to the next packet. This waits
+    /// out a read timeout on an idle line, so it is only done after a start request or before
+    /// a retry, never for every block.
+    fn purge(&mut self) -> Result<()> {
+        while self.channel.read_byte(Duration::ZERO)?.is_some() {
+            self.check_cancelled()?;
+        }
+        Ok(())
+    }
+
+    /// A receiver cancels with two CANs in a row, a lone one is taken as noise
+    fn check_receiver_cancel(&mut self) -> Result<()> {
+        match self.channel.read_byte(TRANSFER_POLL_INTERVAL)? {
+            Some(CAN) => Err(SpewcapError::Transfer("cancelled by the receiver".to_string())),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_cancelled(&self) -> Result<()> {
+        if self.channel.cancelled() {
+            return Err(SpewcapError::Transfer("cancelled".to_string()));
+        }
+        Ok(())
+    }
+
+    fn report_progress(&mut self) {
+        if self.last_report.elapsed() < SEND_PROGRESS_INTERVAL || self.blocks_sent == self.blocks_total {
+            return;
+        }
+        self.last_report = Instant::now();
+        print_message(format!(
+            "{}: block {} of {} ({}%), {} retries",
+            self.protocol,
+            self.blocks_sent,
+            self.blocks_total,
+            self.blocks_sent * 100 / self.blocks_total.max(1),
+            self.retries
+        ));
+    }
+}
+
+/// YMODEM block 0: the file name and its size in decimal, NUL-terminated
+fn header_block(name: &str, size: usize) -> Vec<u8> {
+    let mut block = Vec::with_capacity(SHORT_BLOCK_SIZE);
+    block.extend_from_slice(name.as_bytes());
+    block.push(0);
+    block.extend_from_slice(size.to_string().as_bytes());
+    block.push(0);
+    let size = if block.len() <= SHORT_BLOCK_SIZE { SHORT_BLOCK_SIZE } else { LONG_BLOCK_SIZE };
+    block.resize(size, 0);
+    block
+}
+
+fn packet(number: u8, block: &[u8], crc: bool) -> Vec<u8> {
+    let start = if block.len() == LONG_BLOCK_SIZE { STX } else { SOH };
+    let mut packet = Vec::with_capacity(block.len() + 5);
+    packet.extend_from_slice(&[start, number, !number]);
+    packet.extend_from_slice(block);
+    if crc {
+        packet.extend_from_slice(&crc16(block).to_be_bytes());
+    } else {
+        packet.push(checksum(block));
+    }
+    packet
+}
+
+/// CRC-16/XMODEM: polynomial 0x1021, starting from 0
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0, |crc, &byte| {
+        (0..8).fold(crc ^ (u16::from(byte) << 8), |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum: u8, &byte| sum.wrapping_add(byte))
+}
+
+/// The capture thread's source, borrowed for the length of a transfer
+struct PortChannel<'a> {
+    source: &'a mut dyn Source,
+    shared_state: &'a State,
+    buffer: [u8; SERIAL_READ_BUFFER_SIZE],
+    start: usize,
+    end: usize,
+}
+
+impl<'a> PortChannel<'a> {
+    fn new(source: &'a mut dyn Source, shared_state: &'a State) -> Self {
+        PortChannel {
+            source,
+            shared_state,
+            buffer: [0; SERIAL_READ_BUFFER_SIZE],
+            start: 0,
+            end: 0,
+        }
+    }
+}
+
+impl Channel for PortChannel<'_> {
+    fn read_byte(&mut self, timeout: Duration) -> Result<Option<u8>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if self.start < self.end {
+                self.start += 1;
+                return Ok(Some(self.buffer[self.start - 1]));
+            }
+            // the source is read at least once, even with no time left
+            match self.source.read(&mut self.buffer) {
+                ReadResult::Data(size) => {
+                    self.start = 0;
+                    self.end = size;
+                }
+                ReadResult::NoData if Instant::now() >= deadline => return Ok(None),
+                ReadResult::NoData => std::thread::sleep(SERIAL_NO_DATA_SLEEP),
+                ReadResult::End | ReadResult::Error => return Err(SpewcapError::PortNotConnected),
+            }
+        }
+    }
+
+    fn write_all(&mut self, data: &[u8]) -> Result<()> {
+        serial::write_to_port(self.shared_state, data)
+    }
+
+    fn cancelled(&self) -> bool {
+        self.shared_state.transfer_cancel.load(Ordering::Relaxed) || utils::quit_requested(self.shared_state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::init_state;
+    use crate::transport::SourceWriter;
+    use std::io::{self, Write};
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::mpsc::{self, Receiver, SyncSender};
+    use std::sync::Arc;
+    use std::thread::{self, JoinHandle};
+
+    /// One end of an in-memory link, optionally flipping a bit of the byte written at `corrupt_at`
+    struct TestChannel {
+        incoming: Receiver<u8>,
+        outgoing: SyncSender<u8>,
+        written: usize,
+        corrupt_at: Option<usize>,
+    }
+
+    impl Channel for TestChannel {
+        fn read_byte(&mut self, timeout: Duration) -> Result<Option<u8>> {
+            Ok(self.incoming.recv_timeout(timeout).ok())
+        }
+
+        fn write_all(&mut self, data: &[u8]) -> Result<()> {
+            for &byte in data {
+                let byte = if self.corrupt_at == Some(self.written) { byte ^ 0x01 } else { byte };
+                self.written += 1;
+                self.outgoing
+                    .send(byte)
+                    .map_err(|e| SpewcapError::Transfer(e.to_string()))?;
+            }
+            Ok(())
+        }
+
+        fn cancelled(&self) -> bool {
+            false
+        }
+    }
+
+    fn link(corrupt_at: Option<usize>) -> (TestChannel, TestChannel) {
+        let (to_receiver, from_sender) = mpsc::sync_channel(1 << 16);
+        let (to_sender, from_receiver) = mpsc::sync_channel(1 << 16);
+        let sender = TestChannel {
+            incoming: from_receiver,
+            outgoing: to_receiver,
+            written: 0,
+            corrupt_at,
+        };
+        let receiver = TestChannel {
+            incoming: from_sender,
+            outgoing: to_sender,
+            written: 0,
+            corrupt_at: None,
+        };
+        (sender, receiver)
+    }
+
+    /// A receiver as found in bootloaders, started after it has repeated its start request a few
+    /// times while waiting for the sender. The thread returns the YMODEM file name and the data.
+    fn spawn_receiver(mut channel: TestChannel, ymodem: bool, crc: bool) -> JoinHandle<(Option<String>, Vec<u8>)> {
+        let start = if crc { CRC_REQUEST } else { NAK };
+        channel.write_all(&[start; 3]).unwrap();
+        thread::spawn(move || receive(channel, ymodem, crc))
+    }
+
+    fn receive(mut channel: TestChannel, ymodem: bool, crc: bool) -> (Option<String>, Vec<u8>) {
+        let timeout = Duration::from_secs(5);
+        let read = |channel: &mut TestChannel| channel.read_byte(timeout).unwrap().expect("sender stalled");
+        let mut name = None;
+        let mut size = None;
+        let mut data = Vec::new();
+        let mut expected = if ymodem { 0u8 } else { 1u8 };
+        let mut eot_count = 0;
+        loop {
+            let block_size = match read(&mut channel) {
+                SOH => SHORT_BLOCK_SIZE,
+                STX => LONG_BLOCK_SIZE,
+                EOT => {
+                    eot_count += 1;
+                    if ymodem && eot_count == 1 {
+                        channel.write_all(&[NAK]).unwrap();
+                        continue;
+                    }
+                    channel.write_all(&[ACK]).unwrap();
+                    break;
+                }
+                other => panic!("unexpected byte {other:#04x}"),
+            };
+            let number = read(&mut channel);
+            let complement = read(&mut channel);
+            let block: Vec<u8> = (0..block_size).map(|_| read(&mut channel)).collect();
+            let valid = if crc {
+                let received = u16::from_be_bytes([read(&mut channel), read(&mut channel)]);
+                received == crc16(&block)
+            } else {
+                read(&mut channel) == checksum(&block)
+            };
+            if !valid || complement != !number || number != expected {
+                channel.write_all(&[NAK]).unwrap();
+                continue;
+            }
+            channel.write_all(&[ACK]).unwrap();
+            if ymodem && number == 0 && name.is_none() {
+                let mut fields = block.split(|&byte| byte == 0);
+                name = fields.next().map(|field| String::from_utf8_lossy(field).into_owned());
+                size = fields.next().map(|field| String::from_utf8_lossy(field).parse::<usize>().unwrap());
+                channel.write_all(&[CRC_REQUEST]).unwrap();
+            } else {
+                data.extend_from_slice(&block);
+            }
+            expected = number.wrapping_add(1);
+        }
+        if ymodem {
+            // the empty header closing the batch
+            channel.write_all(&[CRC_REQUEST]).unwrap();
+            assert_eq!(read(&mut channel), SOH);
+            let packet: Vec<u8> = (0..SHORT_BLOCK_SIZE + 4).map(|_| read(&mut channel)).collect();
+            assert!(packet[2..2 + SHORT_BLOCK_SIZE].iter().all(|&byte| byte == 0));
+            channel.write_all(&[ACK]).unwrap();
+            data.truncate(size.unwrap());
+        } else {
+            while data.last() == Some(&SUB) {
+                data.pop();
+            }
+        }
+        (name, data)
+    }
+
+    /// The sender's end of a link as a port, where a read finding nothing waits out a read
+    /// timeout like a serial port does. Those reads are counted.
+    struct LinkSource {
+        incoming: Receiver<u8>,
+        idle_reads: Arc<AtomicUsize>,
+    }
+
+    impl Source for LinkSource {
+        fn name(&self) -> String {
+            "link".to_string()
+        }
+
+        fn summary(&self) -> String {
+            "in-memory link".to_string()
+        }
+
+        fn open(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn close(&mut self) {}
+
+        fn read(&mut self, buffer: &mut [u8]) -> ReadResult {
+            let Ok(first) = self.incoming.recv_timeout(Duration::from_millis(50)) else {
+                self.idle_reads.fetch_add(1, Ordering::Relaxed);
+                return ReadResult::NoData;
+            };
+            buffer[0] = first;
+            let mut size = 1;
+            while let (Some(slot), Ok(byte)) = (buffer.get_mut(size), self.incoming.try_recv()) {
+                *slot = byte;
+                size += 1;
+            }
+            ReadResult::Data(size)
+        }
+
+        fn writer(&self) -> Option<Box<dyn SourceWriter>> {
+            None
+        }
+    }
+
+    struct LinkWriter {
+        outgoing: SyncSender<u8>,
+    }
+
+    impl Write for LinkWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            for &byte in buf {
+                self.outgoing.send(byte).map_err(io::Error::other)?;
+            }
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SourceWriter for LinkWriter {}
+
+    fn image(size: usize) -> Vec<u8> {
+        (0..size).map(|i| (i * 7 % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_crc16() {
+        assert_eq!(crc16(b"123456789"), 0x31c3);
+    }
+
+    #[test]
+    fn test_xmodem_crc_retries_a_corrupted_block() {
+        let data = image(1000);
+        // a byte in the middle of the second packet
+        let (mut sender_end, receiver_end) = link(Some(133 + 60));
+        let receiver = spawn_receiver(receiver_end, false, true);
+        let mut sender = Sender::new(&mut sender_end, Protocol::XmodemCrc);
+        sender.send("image.bin", &data).unwrap();
+        assert_eq!((sender.blocks_sent, sender.retries), (8, 1));
+        assert_eq!(receiver.join().unwrap(), (None, data));
+    }
+
+    #[test]
+    fn test_xmodem_falls_back_to_checksum() {
+        let data = image(300);
+        let (mut sender_end, receiver_end) = link(None);
+        let receiver = spawn_receiver(receiver_end, false, false);
+        Sender::new(&mut sender_end, Protocol::Xmodem1k).send("image.bin", &data).unwrap();
+        assert_eq!(receiver.join().unwrap().1, data);
+    }
+
+    #[test]
+    fn test_port_channel_does_not_wait_before_every_block() {
+        let data = image(20 * LONG_BLOCK_SIZE);
+        let (sender_end, receiver_end) = link(None);
+        let receiver = spawn_receiver(receiver_end, false, true);
+        let idle_reads = Arc::new(AtomicUsize::new(0));
+        let mut source = LinkSource {
+            incoming: sender_end.incoming,
+            idle_reads: Arc::clone(&idle_reads),
+        };
+        let state = init_state();
+        *state.port_writer.lock().unwrap() = Some(Box::new(LinkWriter { outgoing: sender_end.outgoing }));
+        let mut channel = PortChannel::new(&mut source, &state);
+        let mut sender = Sender::new(&mut channel, Protocol::Xmodem1k);
+        sender.send("image.bin", &data).unwrap();
+        assert_eq!(sender.blocks_sent, 20);
+        assert_eq!(receiver.join().unwrap().1, data);
+        // only the purge after the start request should find the line idle
+        assert!(idle_reads.load(Ordering::Relaxed) <= 2);
+    }
+
+    #[test]
+    fn test_ymodem_sends_name_and_size() {
+        // a multiple of the padding byte must survive, which only the size in the header allows
+        let mut data = image(2100);
+        data.extend_from_slice(&[SUB; 20]);
+        let (mut sender_end, receiver_end) = link(None);
+        let receiver = spawn_receiver(receiver_end, true, true);
+        let mut sender = Sender::new(&mut sender_end, Protocol::Ymodem);
+        sender.send("u-boot.itb", &data).unwrap();
+        assert_eq!(sender.blocks_sent, 3);
+        assert_eq!(receiver.join().unwrap(), (Some("u-boot.itb".to_string()), data));
+    }
+}